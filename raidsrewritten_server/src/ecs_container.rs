@@ -1,51 +1,78 @@
-use crate::game::{components::*, condition};
+use crate::game::{components::*, condition, movement, projectile, staleness};
 use crate::game::{mechanics, utils::*};
 use crate::system_messages::MessageToEcs;
 use crate::webserver::message::{Action, Message, UpdatePartyStatusPayload};
 use crate::webserver::metrics::*;
+use crate::webserver::{self, handshake};
 use flecs_ecs::prelude::*;
-use socketioxide::socket::Sid;
-use socketioxide::{SocketIo, socket};
-use std::sync::mpsc::Receiver;
+use socketioxide::{SocketIo, socket::Sid};
 use std::time::Duration;
+use tokio::sync::mpsc::{Receiver, error::TryRecvError};
+use tokio::task::JoinHandle;
 use tokio::time;
-use tracing::info;
-
-struct CommonQueries<'a> {
-    query_socket: Query<&'a Socket>,
-    query_mechanic: Query<(&'a Mechanic, &'a Party)>,
-}
+use tracing::{error, info, warn};
 
 pub fn create_world() -> World {
     World::new()
 }
 
-#[allow(clippy::let_underscore_future)]
-pub fn run_world(world: World, rx_from_ws: Receiver<MessageToEcs>, io: &SocketIo) {
+pub fn run_world(
+    world: World,
+    mut rx_from_ws: Receiver<MessageToEcs>,
+    io: &SocketIo,
+) -> JoinHandle<()> {
     world.set(SocketIoSingleton { io: io.clone() });
-
-    let common_queries = CommonQueries {
-        query_socket: world.query::<&Socket>().set_cached().build(),
-        query_mechanic: world.query::<(&Mechanic, &Party)>().set_cached().build(),
-    };
+    world.set(OutboundMessageQueue::default());
+    world.set(PartyIndex::default());
+    let required = handshake::is_handshake_required();
+    info!(required, "Handshake requirement");
+    world.set(ClientHandshakes {
+        required,
+        ..Default::default()
+    });
 
     create_systems(&world);
     create_observers(&world);
 
-    let _ = tokio::spawn(async move {
+    tokio::spawn(async move {
         let mut interval = time::interval(Duration::from_micros(1_000_000 / 64));
 
         loop {
             interval.tick().await;
-            process_messages(&world, &common_queries, &rx_from_ws);
+            if !process_messages(&world, &mut rx_from_ws) {
+                info!("Webserver channel closed, stopping ECS loop");
+                return;
+            }
             world.progress();
+            flush_outbound_messages(&world);
         }
-    });
+    })
+}
+
+// The ECS loop is expected to run for the lifetime of the server, so it stopping for any reason is treated as fatal.
+// The error is returned to main to exit the process, where it can then be restarted cleanly.
+pub async fn supervise_world(handle: JoinHandle<()>) -> Result<(), Box<dyn std::error::Error>> {
+    match handle.await {
+        Ok(()) => {
+            error!("ECS loop stopped");
+            Err("ECS loop stopped".into())
+        }
+        Err(e) => {
+            error!(error = %e, "ECS loop failed");
+            Err(Box::new(e))
+        }
+    }
 }
 
-fn process_messages(world: &World, queries: &CommonQueries, rx_from_ws: &Receiver<MessageToEcs>) {
+// Returns false if the webserver side of the channel has closed
+fn process_messages(world: &World, rx_from_ws: &mut Receiver<MessageToEcs>) -> bool {
     // Receive messages from the webserver system per game tick
-    for message in rx_from_ws.try_iter() {
+    loop {
+        let message = match rx_from_ws.try_recv() {
+            Ok(m) => m,
+            Err(TryRecvError::Empty) => return true,
+            Err(TryRecvError::Disconnected) => return false,
+        };
         match message {
             MessageToEcs::UpdatePlayer {
                 socket_id,
@@ -54,8 +81,13 @@ fn process_messages(world: &World, queries: &CommonQueries, rx_from_ws: &Receive
                 role,
                 party,
             } => {
+                if is_missing_required_handshake(world, socket_id, Action::UpdatePlayer) {
+                    continue;
+                }
                 let player_entity;
-                if let Some(e) = find_socket(&queries.query_socket, socket_id) {
+                let mut previous_party: Option<String> = None;
+                let mut switched_party = false;
+                if let Some(e) = find_socket(&world.world(), socket_id) {
                     info!(
                         socket_str = socket_id.as_str(),
                         content_id,
@@ -65,14 +97,24 @@ fn process_messages(world: &World, queries: &CommonQueries, rx_from_ws: &Receive
                         "Updating Player"
                     );
                     player_entity = e;
-                    player_entity.remove((flecs::ChildOf, flecs::Wildcard::ID));
+                    if let Some(pc) = player_entity.parent() {
+                        previous_party = pc.try_get::<&Party>(|p| p.id.clone());
+                        if previous_party.as_deref() != Some(party.as_str()) {
+                            leave_party(world, player_entity, pc);
+                            switched_party = true;
+                        }
+                    }
                 } else {
+                    let legacy_client = world.get::<&ClientHandshakes>(|handshakes| {
+                        !handshakes.sockets.contains_key(&socket_id)
+                    });
                     info!(
                         socket_str = socket_id.as_str(),
                         content_id,
                         name,
                         role_str = Into::<&str>::into(&role),
                         party,
+                        legacy_client,
                         "Adding Player"
                     );
                     player_entity = world.entity();
@@ -80,29 +122,51 @@ fn process_messages(world: &World, queries: &CommonQueries, rx_from_ws: &Receive
                     CONNECTED_PLAYERS_TOTAL.inc();
                 }
 
+                // Move the socket into its party's room, so that party broadcasts reach it
+                if let Some(io) = get_socket_io(&world.world())
+                    && let Some(socket) = io.get_socket(socket_id)
+                {
+                    if let Some(previous_party) = &previous_party
+                        && *previous_party != party
+                    {
+                        socket.leave(party_room(previous_party));
+                    }
+                    socket.join(party_room(&party));
+                }
+
+                let party_container = match find_party_container(&world.world(), &party) {
+                    Some(pc) => pc,
+                    None => world
+                        .entity()
+                        .add(PartyContainer)
+                        .set(Party { id: party.clone() }),
+                };
+
+                // Parent the player first, so that the party container's children are up to date
+                // by the time the Player OnSet observer runs
                 player_entity
+                    .child_of(party_container)
                     .set(Socket { id: socket_id })
-                    .set(Player { content_id, name })
                     .set(Role { role })
-                    .set(Party { id: party.clone() });
+                    .set(Player { content_id, name });
 
-                let party_container;
-                if let Some(pc) = find_party_container(world, &party) {
-                    party_container = pc;
-                } else {
-                    party_container = world.entity().set(Party { id: party }).add(PartyContainer);
-                };
-                player_entity.child_of(party_container);
+                // The new party hasn't seen this player's conditions yet
+                if switched_party {
+                    party_container.add(BroadcastConditions);
+                }
             }
 
             MessageToEcs::UpdateStatus {
                 socket_id,
+                received_at,
                 world_position_x,
                 world_position_y,
                 world_position_z,
+                rotation,
+                movement_flags,
                 is_alive,
             } => {
-                if let Some(e) = find_socket(&queries.query_socket, socket_id) {
+                if let Some(e) = find_socket(&world.world(), socket_id) {
                     info!(
                         socket_str = socket_id.as_str(),
                         world_position_x,
@@ -111,29 +175,55 @@ fn process_messages(world: &World, queries: &CommonQueries, rx_from_ws: &Receive
                         is_alive,
                         "Updating PlayerStatus"
                     );
-                    e.set(Position {
+                    let position = Position {
                         x: world_position_x,
                         y: world_position_y,
                         z: world_position_z,
-                    })
-                    .set(State { is_alive });
+                    };
+                    e.set(position)
+                        .set(State { is_alive })
+                        .set(LastStatusUpdate { time: received_at });
+                    movement::record_position_sample(&e, position, received_at);
+                    if let Some(rotation) = rotation {
+                        e.set(Rotation { value: rotation });
+                    }
+                    if let Some(flags) = movement_flags {
+                        movement::record_movement_flags(&e, MovementFlags(flags), received_at);
+                    }
+                    if e.has(Stale::id()) {
+                        e.remove(Stale::id());
+                        if let Some(party_id) = e
+                            .parent()
+                            .and_then(|pc| pc.try_get::<&Party>(|p| p.id.clone()))
+                        {
+                            on_player_update(&world.world(), &party_id, None);
+                        }
+                    }
                 }
             }
 
             MessageToEcs::RemovePlayer { socket_id } => {
-                world.defer(|| {
-                    queries.query_socket.each_entity(|e, socket| {
-                        if socket.id == socket_id {
-                            e.get::<(Option<&Player>, Option<&Role>)>(|(player, role)| {
-                                info!(
-                                    socket_str = socket_id.as_str(),
-                                    "Removing Player {:?} {:?}", player, role
-                                );
-                            });
-                            e.destruct();
-                            CONNECTED_PLAYERS.dec();
-                        }
+                world.get::<&mut ClientHandshakes>(|handshakes| {
+                    handshakes.sockets.remove(&socket_id);
+                });
+                if let Some(e) = find_socket(&world.world(), socket_id) {
+                    e.get::<(Option<&Player>, Option<&Role>)>(|(player, role)| {
+                        info!(
+                            socket_str = socket_id.as_str(),
+                            "Removing Player {:?} {:?}", player, role
+                        );
                     });
+                    e.destruct();
+                    CONNECTED_PLAYERS.dec();
+                }
+            }
+
+            MessageToEcs::CompleteHandshake {
+                socket_id,
+                capabilities,
+            } => {
+                world.get::<&mut ClientHandshakes>(|handshakes| {
+                    handshakes.sockets.insert(socket_id, capabilities);
                 });
             }
 
@@ -146,16 +236,24 @@ fn process_messages(world: &World, queries: &CommonQueries, rx_from_ws: &Receive
                 world_position_z,
                 rotation,
                 extra_data,
+                vertical_extent_below,
+                vertical_extent_above,
             } => {
-                let Some(e) = find_socket(&queries.query_socket, socket_id) else {
-                    return;
+                if is_missing_required_handshake(world, socket_id, Action::StartMechanic) {
+                    continue;
+                }
+                let Some(e) = find_socket(&world.world(), socket_id) else {
+                    continue;
                 };
-                e.try_get::<&Party>(|party| {
-                    if queries
-                        .query_mechanic
-                        .find(|(m, p)| m.request_id == request_id && p.id == party.id)
-                        .is_none()
-                    {
+                let Some(pc) = e.parent() else {
+                    continue;
+                };
+                pc.try_get::<&Party>(|party| {
+                    let mut exists = false;
+                    pc.each_child(|c| {
+                        c.try_get::<&Mechanic>(|m| exists |= m.request_id == request_id);
+                    });
+                    if !exists {
                         info!(
                             socket_str = socket_id.as_str(),
                             party.id, request_id, mechanic_id, "Adding Mechanic"
@@ -166,12 +264,17 @@ fn process_messages(world: &World, queries: &CommonQueries, rx_from_ws: &Receive
                             world_position_z,
                             rotation,
                         );
+                        let vertical_extent = convert_to_vertical_extent(
+                            vertical_extent_below,
+                            vertical_extent_above,
+                        );
                         mechanics::create_mechanic(
                             world,
                             request_id,
                             mechanic_id,
-                            party.id.clone(),
+                            *pc,
                             transform,
+                            vertical_extent,
                             extra_data,
                         );
                         MECHANICS_STARTED
@@ -182,28 +285,35 @@ fn process_messages(world: &World, queries: &CommonQueries, rx_from_ws: &Receive
             }
 
             MessageToEcs::ClearMechanics { socket_id } => {
-                let Some(e) = find_socket(&queries.query_socket, socket_id) else {
-                    return;
+                let Some(e) = find_socket(&world.world(), socket_id) else {
+                    continue;
+                };
+                let Some(pc) = e.parent() else {
+                    continue;
                 };
-                e.try_get::<&Party>(|party| {
+                pc.try_get::<&Party>(|party| {
                     info!(
                         socket_str = socket_id.as_str(),
                         party.id, "Clearing Mechanics"
                     );
-                    queries.query_mechanic.each_entity(|e, (_, p)| {
-                        if p.id == party.id {
-                            e.destruct();
-                        }
-                    });
                 });
+                let mut mechanics: Vec<Entity> = Vec::new();
+                pc.each_child(|c| {
+                    if c.has(Mechanic::id()) {
+                        mechanics.push(*c);
+                    }
+                });
+                for m in mechanics {
+                    m.entity_view(world).destruct();
+                }
             }
 
             MessageToEcs::SyncConditionsOnSelf {
                 socket_id,
                 conditions,
             } => {
-                let Some(e) = find_socket(&queries.query_socket, socket_id) else {
-                    return;
+                let Some(e) = find_socket(&world.world(), socket_id) else {
+                    continue;
                 };
                 info!(
                     socket_str = socket_id.as_str(),
@@ -268,11 +378,11 @@ fn process_messages(world: &World, queries: &CommonQueries, rx_from_ws: &Receive
             }
 
             MessageToEcs::ClearConditions { socket_id } => {
-                let Some(e) = find_socket(&queries.query_socket, socket_id) else {
-                    return;
+                let Some(e) = find_socket(&world.world(), socket_id) else {
+                    continue;
                 };
                 let Some(party_container) = e.parent() else {
-                    return;
+                    continue;
                 };
                 let mut removed_any = false;
                 party_container.each_child(|pc_child| {
@@ -294,101 +404,216 @@ fn process_messages(world: &World, queries: &CommonQueries, rx_from_ws: &Receive
     }
 }
 
+// Once a handshake is required, legacy clients are rejected and disconnected instead of being served
+fn is_missing_required_handshake(world: &World, socket_id: Sid, action: Action) -> bool {
+    let missing = world.get::<&ClientHandshakes>(|handshakes| {
+        handshakes.required && !handshakes.sockets.contains_key(&socket_id)
+    });
+    if missing {
+        let action: &'static str = action.into();
+        warn!(
+            socket_str = socket_id.as_str(),
+            action, "Rejected message from a socket without a handshake"
+        );
+        INBOUND_MESSAGES_REJECTED
+            .with_label_values(&[action, "no_handshake"])
+            .inc();
+        if let Some(io) = get_socket_io(&world.world())
+            && let Some(socket) = io.get_socket(socket_id)
+        {
+            webserver::reject_socket(
+                socket,
+                handshake::rejection("A handshake is required".to_string()),
+            );
+        }
+    }
+    missing
+}
+
 fn create_systems(world: &World) {
     mechanics::create_systems(world);
+    projectile::create_systems(world);
     condition::create_systems(world);
+    staleness::create_systems(world);
 }
 
 fn create_observers(world: &World) {
     mechanics::create_observers(world);
+    projectile::create_observers(world);
 
-    // Send UpdatePartyStatus to all party members when a player joins or leaves
+    // Maintain the PartyIndex, so that lookups don't have to build and scan a query
     world
         .observer::<flecs::OnSet, &Party>()
-        .with(Player::id())
+        .with(PartyContainer)
+        .each_entity(|e, party| {
+            e.world().get::<&mut PartyIndex>(|index| {
+                index.party_containers.insert(party.id.clone(), *e);
+            });
+        });
+    world
+        .observer::<flecs::OnRemove, &Party>()
+        .with(PartyContainer)
+        .each_entity(|e, party| {
+            e.world().get::<&mut PartyIndex>(|index| {
+                if index.party_containers.get(&party.id) == Some(&*e) {
+                    index.party_containers.remove(&party.id);
+                }
+            });
+        });
+    world
+        .observer::<flecs::OnSet, &Player>()
+        .each_entity(|e, player| {
+            e.world().get::<&mut PartyIndex>(|index| {
+                index.players.insert(player.content_id, *e);
+            });
+        });
+    world
+        .observer::<flecs::OnRemove, &Player>()
+        .each_entity(|e, player| {
+            e.world().get::<&mut PartyIndex>(|index| {
+                if index.players.get(&player.content_id) == Some(&*e) {
+                    index.players.remove(&player.content_id);
+                }
+            });
+        });
+    world
+        .observer::<flecs::OnSet, &Socket>()
+        .each_entity(|e, socket| {
+            e.world().get::<&mut PartyIndex>(|index| {
+                index.sockets.insert(socket.id, *e);
+            });
+        });
+    world
+        .observer::<flecs::OnRemove, &Socket>()
+        .each_entity(|e, socket| {
+            e.world().get::<&mut PartyIndex>(|index| {
+                if index.sockets.get(&socket.id) == Some(&*e) {
+                    index.sockets.remove(&socket.id);
+                }
+            });
+        });
+
+    // Send UpdatePartyStatus to all party members when a player joins or leaves
+    // Party membership is the player's ChildOf relationship to a PartyContainer, which holds the Party
+    world
+        .observer::<flecs::OnSet, (&Player, &Party)>()
+        .term_at(1)
+        .up()
         .filter()
-        .each_iter(|it, _, pa1| {
-            on_player_update(&it.world(), it.event(), &pa1.id, None);
+        .each_iter(|it, _, (_, pa1)| {
+            on_player_update(&it.world(), &pa1.id, None);
         });
     world
         .observer::<flecs::OnRemove, (&Player, &Party)>()
+        .term_at(1)
+        .up()
+        .filter()
         .each_iter(|it, _, (pl1, pa1)| {
-            on_player_update(&it.world(), it.event(), &pa1.id, Some(pl1.content_id));
+            on_player_update(&it.world(), &pa1.id, Some(pl1.content_id));
+        });
+
+    // Let the party know when a player goes stale. Recovering is handled in UpdateStatus, since Stale is also
+    // removed when the player is destructed, which the Player OnRemove observer already reports.
+    world
+        .observer::<flecs::OnAdd, (&Player, &Party)>()
+        .with(Stale)
+        .term_at(0)
+        .filter()
+        .term_at(1)
+        .up()
+        .filter()
+        .each_iter(|it, _, (_, pa1)| {
+            on_player_update(&it.world(), &pa1.id, None);
         });
 
     // Cleanup party entities when the last player in the party leaves
     world
-        .observer::<flecs::OnRemove, (&Player, &Party)>()
-        .each_iter(|it, _, (player, party)| {
-            let last_player = it
-                .world()
-                .query::<(&Player, &Party)>()
-                .build()
-                .find(|(pl, pa)| pa.id == party.id && pl.content_id != player.content_id)
-                .is_none();
+        .observer::<flecs::OnRemove, &Player>()
+        .with(PartyContainer)
+        .up()
+        .filter()
+        .each_iter(|it, index, player| {
+            let Some(pc) = it.entity(index).parent() else {
+                return;
+            };
+            let last_player = players_in_party(&pc, Some(player.content_id)) == 0;
             info!(player.name, last_player, "Player removed");
             if last_player {
-                // Cleanup any party entities
-                it.world().query::<&Party>().build().each_entity(|e, p| {
-                    if p.id == party.id {
-                        e.destruct();
-                    }
-                });
+                // Destructing the party container also destructs every entity in the party
+                pc.destruct();
+            } else {
+                mechanics::remove_player_from_mechanics(&pc, *it.entity(index));
             }
         });
 }
 
-fn find_socket<'a>(query: &Query<&'a Socket>, socket_id: socket::Sid) -> Option<EntityView<'a>> {
-    query.find(|socket| socket.id == socket_id)
+// Moves a player out of its current party. The remaining members are notified, mechanics stop
+// referencing the player, and the party is cleaned up if nobody is left in it.
+fn leave_party(world: &World, player_entity: EntityView<'_>, pc: EntityView<'_>) {
+    let Some(party_id) = pc.try_get::<&Party>(|p| p.id.clone()) else {
+        return;
+    };
+    player_entity.remove((flecs::ChildOf, *pc));
+    mechanics::remove_player_from_mechanics(&pc, *player_entity);
+
+    let players_left = players_in_party(&pc, None);
+    info!(party_id, players_left, "Player left party");
+    if players_left == 0 {
+        // Destructing the party container also destructs every entity in the party
+        pc.destruct();
+    } else {
+        on_player_update(&world.world(), &party_id, None);
+        // The player's conditions should no longer be shown to the old party
+        pc.add(BroadcastConditions);
+    }
 }
 
-fn on_player_update(
-    world: &WorldRef<'_>,
-    event: EntityView<'_>,
-    party_id: &String,
-    removed_player_id: Option<u64>,
-) {
-    let mut socket_ids: Vec<Sid> = Vec::new();
-    world
-        .query::<(&Socket, &Player, &Party)>()
-        .build()
-        .each(|(s, pl2, pa2)| {
-            // During OnRemove, the entity isn't actually gone yet
-            if event == flecs::OnRemove::ID
-                && let Some(player_id) = removed_player_id
-                && pl2.content_id == player_id
-            {
+// During OnRemove, the removed player isn't actually gone yet, so it can be excluded from the count
+fn players_in_party(pc: &EntityView<'_>, excluded_player_id: Option<u64>) -> u8 {
+    let mut players_in_party: u8 = 0;
+    pc.each_child(|c| {
+        c.try_get::<(&Socket, &Player)>(|(_, pl)| {
+            if excluded_player_id == Some(pl.content_id) {
                 return;
             }
-            if pa2.id == *party_id {
-                socket_ids.push(s.id);
-            }
+            // This is technically able to overflow, but shouldn't under normal circumstances
+            players_in_party = players_in_party.saturating_add(1);
         });
+    });
+    players_in_party
+}
 
-    if socket_ids.is_empty() {
+fn on_player_update(world: &WorldRef<'_>, party_id: &str, removed_player_id: Option<u64>) {
+    let Some(pc) = find_party_container(world, party_id) else {
+        return;
+    };
+    let players_in_party = players_in_party(&pc, removed_player_id);
+    if players_in_party == 0 {
         return;
     }
-    // This conversion is technically able to overflow, but shouldn't under normal circumstances
-    // https://stackoverflow.com/a/28280042
-    let players_in_party = socket_ids.len() as u8;
-
-    let io = get_socket_io(world);
-    for sid in socket_ids {
-        let io = io.clone();
-        tokio::spawn(async move {
-            io.to(sid)
-                .emit(
-                    "message",
-                    &Message {
-                        action: Action::UpdatePartyStatus,
-                        update_party_status: Some(UpdatePartyStatusPayload {
-                            connected_players_in_party: players_in_party,
-                        }),
-                        ..Default::default()
-                    },
-                )
-                .await
-                .unwrap();
+
+    let mut stale_players: Vec<u64> = Vec::new();
+    pc.each_child(|c| {
+        if !c.has(Stale::id()) {
+            return;
+        }
+        c.try_get::<&Player>(|pl| {
+            if removed_player_id != Some(pl.content_id) {
+                stale_players.push(pl.content_id);
+            }
         });
-    }
+    });
+
+    broadcast_message(
+        world,
+        party_id,
+        Message {
+            action: Action::UpdatePartyStatus,
+            update_party_status: Some(UpdatePartyStatusPayload {
+                connected_players_in_party: players_in_party,
+                stale_players,
+            }),
+            ..Default::default()
+        },
+    );
 }
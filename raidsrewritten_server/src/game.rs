@@ -1,5 +1,8 @@
 pub mod components;
 pub mod condition;
 pub mod mechanics;
+pub mod movement;
+pub mod projectile;
 pub mod role;
+pub mod staleness;
 pub mod utils;
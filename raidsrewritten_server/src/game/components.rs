@@ -1,13 +1,47 @@
-use crate::game::{condition, role};
+use crate::{
+    game::{condition, role},
+    webserver::{handshake::ClientCapabilities, message::Message},
+};
 use flecs_ecs::prelude::*;
 use socketioxide::{SocketIo, socket::Sid};
-use std::collections::HashMap;
+use std::{
+    collections::{HashMap, VecDeque},
+    time::Instant,
+};
 
 #[derive(Component)]
 pub struct SocketIoSingleton {
     pub io: SocketIo,
 }
 
+pub enum OutboundTarget {
+    Socket(Sid),
+    Party(String),
+}
+
+// Messages to each socket and party are queued here in order during a tick, and sent together once the tick ends
+#[derive(Component, Default)]
+pub struct OutboundMessageQueue {
+    pub messages: Vec<(OutboundTarget, Message)>,
+}
+
+// Lookups from ids to entities, maintained by observers as the entities change
+#[derive(Component, Default)]
+pub struct PartyIndex {
+    pub party_containers: HashMap<String, Entity>,
+    pub players: HashMap<u64, Entity>,
+    pub sockets: HashMap<Sid, Entity>,
+}
+
+// What each socket reported supporting in its handshake.
+// Sockets without an entry are legacy clients that never sent one.
+#[derive(Component, Default)]
+pub struct ClientHandshakes {
+    pub sockets: HashMap<Sid, ClientCapabilities>,
+    // Whether legacy clients are turned away instead of served
+    pub required: bool,
+}
+
 #[derive(Component)]
 pub struct Socket {
     pub id: Sid,
@@ -39,6 +73,60 @@ pub struct Position {
     pub z: f32,
 }
 
+// Server time of the player's last UpdateStatus
+#[derive(Component, Debug)]
+pub struct LastStatusUpdate {
+    pub time: Instant,
+}
+
+// Recent reported positions, used to estimate Velocity and to look up where players were in the past
+#[derive(Component, Default, Debug)]
+pub struct PositionHistory {
+    pub samples: VecDeque<(Instant, Position)>,
+}
+
+// In units per second. For players, this is estimated from PositionHistory.
+#[derive(Component, Clone, Copy, Default, Debug)]
+pub struct Velocity {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+// Reported by the client in UpdateStatus
+#[derive(Component, Clone, Copy, Default, Debug, PartialEq)]
+pub struct MovementFlags(pub u8);
+
+impl MovementFlags {
+    pub const JUMPING: u8 = 1 << 0;
+    pub const MOUNTED: u8 = 1 << 1;
+    pub const CASTING: u8 = 1 << 2;
+    pub const SPRINTING: u8 = 1 << 3;
+    pub const ALL: u8 = Self::JUMPING | Self::MOUNTED | Self::CASTING | Self::SPRINTING;
+
+    pub fn contains(self, flag: u8) -> bool {
+        self.0 & flag != 0
+    }
+}
+
+// Server times at which the player's MovementFlags changed, used to check a player's state at a past time
+#[derive(Component, Default, Debug)]
+pub struct MovementFlagsHistory {
+    pub samples: VecDeque<(Instant, MovementFlags)>,
+}
+
+// Added to players whose LastStatusUpdate is older than STALE_STATUS_THRESHOLD, removed on their next UpdateStatus
+#[derive(Component, Debug)]
+pub struct Stale;
+
+// Optional vertical bounds of a mechanic's AoE, relative to the AoE's own y.
+// AoEs without it hit regardless of height.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct VerticalExtent {
+    pub below: f32,
+    pub above: f32,
+}
+
 #[derive(Component, Debug)]
 pub struct Rotation {
     pub value: f32,
@@ -77,6 +165,28 @@ pub struct Affects {
     pub player_entities: HashMap<Entity, u8>,
 }
 
+// What a mechanic does when one of its targets leaves the party or disconnects.
+// Mechanics without this component drop the target.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum TargetLeavePolicy {
+    // Remove the player from the mechanic's targets and affects
+    #[default]
+    Drop,
+    // Replace the player with a random alive, non-stale party member who isn't already targeted, or drop if there is none
+    Transfer,
+    // Cancel the whole mechanic
+    Fail,
+}
+
+#[derive(Component, Debug)]
+pub struct OnTargetLeave {
+    pub policy: TargetLeavePolicy,
+}
+
+// Added to a mechanic when its Targets were changed by a Transfer, so that it can update any target vfx
+#[derive(Component, Debug)]
+pub struct Retargeted;
+
 #[derive(Debug)]
 pub struct Transform {
     pub x: f32,
@@ -124,4 +234,10 @@ pub mod conditions {
     pub struct Hysteria {
         pub redirection_interval: f32,
     }
+
+    // How many times a stacking condition has been applied, see apply_condition_stack
+    #[derive(Component, Debug)]
+    pub struct Stacks {
+        pub count: u8,
+    }
 }
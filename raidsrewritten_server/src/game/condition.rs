@@ -1,7 +1,7 @@
 use flecs_ecs::core::World;
 use flecs_ecs::prelude::*;
 use serde_repr::*;
-use strum_macros::IntoStaticStr;
+use strum_macros::{EnumIter, IntoStaticStr};
 use tracing::info;
 
 use crate::{
@@ -16,7 +16,15 @@ use crate::{
 };
 
 #[derive(
-    Serialize_repr, Deserialize_repr, PartialEq, IntoStaticStr, Default, Copy, Clone, Debug,
+    Serialize_repr,
+    Deserialize_repr,
+    PartialEq,
+    IntoStaticStr,
+    EnumIter,
+    Default,
+    Copy,
+    Clone,
+    Debug,
 )]
 #[repr(u32)]
 pub enum Condition {
@@ -75,9 +83,8 @@ pub fn create_systems(world: &World) {
         .system::<&Party>()
         .with(BroadcastConditions)
         .with(PartyContainer)
-        .each_iter(|it, i, _| {
+        .each_iter(|it, i, party| {
             let pc = it.entity(i);
-            let io = get_socket_io(&it.world());
             let mut players: Vec<UpdateConditionsPlayer> = Vec::new();
 
             pc.each_child(|c1| {
@@ -96,22 +103,16 @@ pub fn create_systems(world: &World) {
                 });
             });
 
-            pc.each_child(|c| {
-                c.try_get::<(&Socket, &Player)>(|(s, _)| {
-                    info!(socket_str = s.id.as_str(), "Sending update_conditions");
-                    send_message(
-                        io.clone(),
-                        s.id,
-                        Message {
-                            action: Action::UpdateConditions,
-                            update_conditions: Some(UpdateConditionsPayload {
-                                players: players.clone(),
-                            }),
-                            ..Default::default()
-                        },
-                    );
-                });
-            });
+            info!(party.id, "Broadcasting update_conditions");
+            broadcast_message(
+                &it.world(),
+                &party.id,
+                Message {
+                    action: Action::UpdateConditions,
+                    update_conditions: Some(UpdateConditionsPayload { players }),
+                    ..Default::default()
+                },
+            );
 
             pc.remove(BroadcastConditions);
         });
@@ -155,6 +156,25 @@ pub fn apply_condition(
     }
 }
 
+// Applies another stack of a condition, up to max_stacks, and refreshes its duration
+pub fn apply_condition_stack(
+    target: &EntityView<'_>,
+    id: u128,
+    condition: Condition,
+    duration: f32,
+    max_stacks: u8,
+) -> Entity {
+    let world = target.world();
+    let entity = apply_condition(target, id, condition, duration, true).entity_view(world);
+    let count = entity
+        .try_get::<&components::conditions::Stacks>(|s| s.count)
+        .unwrap_or(0);
+    entity.set(components::conditions::Stacks {
+        count: count.saturating_add(1).min(max_stacks.max(1)),
+    });
+    *entity
+}
+
 fn build_condition_details(
     entity: EntityView<'_>,
     condition: &components::Condition,
@@ -182,5 +202,9 @@ fn build_condition_details(
         uccd.hysteria_redirection_interval = Some(h.redirection_interval);
     });
 
+    entity.try_get::<&components::conditions::Stacks>(|s| {
+        uccd.stacks = Some(s.count);
+    });
+
     uccd
 }
@@ -2,8 +2,32 @@
 pub mod m0001_spread;
 #[path = "mechanics/0010-enumeration.rs"]
 pub mod m0010_enumeration;
+#[path = "mechanics/0011-stack.rs"]
+pub mod m0011_stack;
 #[path = "mechanics/0020-explosive_trap.rs"]
 pub mod m0020_explosive_trap;
+#[path = "mechanics/0025-gaze.rs"]
+pub mod m0025_gaze;
+#[path = "mechanics/0030-jumpable_shockwave.rs"]
+pub mod m0030_jumpable_shockwave;
+#[path = "mechanics/0040-tower.rs"]
+pub mod m0040_tower;
+#[path = "mechanics/0050-exaflare.rs"]
+pub mod m0050_exaflare;
+#[path = "mechanics/0060-rolling_ball.rs"]
+pub mod m0060_rolling_ball;
+#[path = "mechanics/0070-persistent_hazard.rs"]
+pub mod m0070_persistent_hazard;
+#[path = "mechanics/0080-tether.rs"]
+pub mod m0080_tether;
+#[path = "mechanics/0090-chain_lightning.rs"]
+pub mod m0090_chain_lightning;
+#[path = "mechanics/0100-donut.rs"]
+pub mod m0100_donut;
+#[path = "mechanics/0110-rotating_sweep.rs"]
+pub mod m0110_rotating_sweep;
+#[path = "mechanics/0120-bait_puddle.rs"]
+pub mod m0120_bait_puddle;
 #[path = "mechanics/1000-tea_fire_tornado_1.rs"]
 pub mod m1000_tea_fire_tornado_1;
 #[path = "mechanics/1010-tea_hawk_blaster_tower.rs"]
@@ -26,33 +50,57 @@ use crate::{
     webserver::message::StopVfxPayload,
 };
 use flecs_ecs::prelude::*;
+use rand::seq::IndexedRandom;
 use tracing::info;
 
+type CreateMechanicFn = for<'a> fn(EntityView<'a>) -> EntityView<'a>;
+
+// Every mechanic the server can run, keyed by the mechanic_id sent in StartMechanic
+pub const MECHANICS: &[(u32, CreateMechanicFn)] = &[
+    (1, m0001_spread::create_mechanic),
+    (10, m0010_enumeration::create_mechanic),
+    (11, m0011_stack::create_mechanic),
+    (20, m0020_explosive_trap::create_mechanic),
+    (25, m0025_gaze::create_mechanic),
+    (30, m0030_jumpable_shockwave::create_mechanic),
+    (40, m0040_tower::create_mechanic),
+    (50, m0050_exaflare::create_mechanic),
+    (60, m0060_rolling_ball::create_mechanic),
+    (70, m0070_persistent_hazard::create_mechanic),
+    (80, m0080_tether::create_mechanic),
+    (90, m0090_chain_lightning::create_mechanic),
+    (100, m0100_donut::create_mechanic),
+    (110, m0110_rotating_sweep::create_mechanic),
+    (120, m0120_bait_puddle::create_mechanic),
+    // TEA
+    (1000, m1000_tea_fire_tornado_1::create_mechanic),
+    (1010, m1010_tea_hawk_blaster_tower::create_mechanic),
+    (1011, m1011_tea_blassty_charge_hit::create_mechanic),
+    (1012, m1012_tea_limit_cut_end::create_mechanic),
+    (1020, m1020_tea_spawn_shanoa::create_mechanic),
+    (
+        1021,
+        m1021_tea_show_shanoa_guidance_markers::create_mechanic,
+    ),
+    (1022, m1022_tea_move_shanoa::create_mechanic),
+    (1023, m1023_tea_fire_tornado_attack_shanoa::create_mechanic),
+];
+
 pub fn create_mechanic(
     world: &World,
     request_id: String,
     mechanic_id: u32,
-    party: String,
+    party_container: Entity,
     transform: Option<Transform>,
+    vertical_extent: Option<VerticalExtent>,
     extra_data: Option<String>,
 ) -> Option<EntityView<'_>> {
-    let mechanic_fn: Option<fn(EntityView<'_>) -> EntityView<'_>> = match mechanic_id {
-        1 => Some(m0001_spread::create_mechanic),
-        10 => Some(m0010_enumeration::create_mechanic),
-        20 => Some(m0020_explosive_trap::create_mechanic),
-        // TEA
-        1000 => Some(m1000_tea_fire_tornado_1::create_mechanic),
-        1010 => Some(m1010_tea_hawk_blaster_tower::create_mechanic),
-        1011 => Some(m1011_tea_blassty_charge_hit::create_mechanic),
-        1012 => Some(m1012_tea_limit_cut_end::create_mechanic),
-        1020 => Some(m1020_tea_spawn_shanoa::create_mechanic),
-        1021 => Some(m1021_tea_show_shanoa_guidance_markers::create_mechanic),
-        1022 => Some(m1022_tea_move_shanoa::create_mechanic),
-        1023 => Some(m1023_tea_fire_tornado_attack_shanoa::create_mechanic),
-        _ => None,
-    };
+    let mechanic_fn = MECHANICS
+        .iter()
+        .find(|(id, _)| *id == mechanic_id)
+        .map(|(_, f)| *f);
     if let Some(f) = mechanic_fn {
-        let e = create_generic_mechanic(world, request_id, mechanic_id, party);
+        let e = create_generic_mechanic(world, request_id, mechanic_id, party_container);
 
         if let Some(t) = transform {
             e.set(Position {
@@ -63,6 +111,10 @@ pub fn create_mechanic(
             .set(Rotation { value: t.rotation });
         }
 
+        if let Some(ve) = vertical_extent {
+            e.set(ve);
+        }
+
         if let Some(ed) = extra_data {
             e.set(ExtraMechanicData { value: ed });
         }
@@ -78,30 +130,107 @@ pub fn create_generic_mechanic(
     world: &World,
     request_id: String,
     mechanic_id: u32,
-    party: String,
+    party_container: Entity,
 ) -> EntityView<'_> {
-    let e = world
-        .entity()
-        .set(Mechanic {
-            request_id,
-            mechanic_id,
-        })
-        .set(Party { id: party.clone() });
-
-    if let Some(pc) = find_party_container(world, &party) {
-        e.child_of(pc);
-    }
+    // Mechanics belong to their party through the ChildOf relationship, and read the Party from the container
+    world.entity().child_of(party_container).set(Mechanic {
+        request_id,
+        mechanic_id,
+    })
+}
+
+// Called when a player leaves the party or disconnects, so that the party's mechanics stop referencing
+// the player. Each mechanic handles this according to its OnTargetLeave policy.
+pub fn remove_player_from_mechanics(party_container: &EntityView<'_>, player: Entity) {
+    let mut mechanics: Vec<Entity> = Vec::new();
+    let mut candidates: Vec<Entity> = Vec::new();
+    party_container.each_child(|c| {
+        if c.has(Mechanic::id()) {
+            mechanics.push(*c);
+        } else if *c != player && !c.has(Stale::id()) {
+            c.try_get::<(&Player, &State)>(|(_, s)| {
+                if s.is_alive {
+                    candidates.push(*c);
+                }
+            });
+        }
+    });
+
+    let world = party_container.world();
+    for m in mechanics {
+        let m = m.entity_view(world);
+        let policy = m
+            .try_get::<&OnTargetLeave>(|o| o.policy)
+            .unwrap_or_default();
+        let is_target = m
+            .try_get::<&Targets>(|t| t.player_entities.contains(&player))
+            .unwrap_or(false);
+
+        if is_target && policy == TargetLeavePolicy::Fail {
+            m.get::<&Mechanic>(|mechanic| {
+                info!(
+                    mechanic.request_id,
+                    mechanic.mechanic_id, "Failing Mechanic, a target left"
+                );
+            });
+            m.destruct();
+            continue;
+        }
+
+        if is_target {
+            let mut retargeted = false;
+            m.get::<&mut Targets>(|t| {
+                let replacement = if policy == TargetLeavePolicy::Transfer {
+                    let available: Vec<Entity> = candidates
+                        .iter()
+                        .filter(|c| !t.player_entities.contains(c))
+                        .copied()
+                        .collect();
+                    available.choose(&mut rand::rng()).copied()
+                } else {
+                    None
+                };
+                match replacement {
+                    Some(r) => {
+                        for e in t.player_entities.iter_mut().filter(|e| **e == player) {
+                            *e = r;
+                        }
+                        retargeted = true;
+                    }
+                    None => t.player_entities.retain(|e| *e != player),
+                }
+            });
+            if retargeted {
+                m.add(Retargeted);
+            }
+        }
 
-    e
+        m.try_get::<Option<&mut Affects>>(|affects| {
+            if let Some(a) = affects {
+                a.player_entities.remove(&player);
+            }
+        });
+    }
 }
 
 pub fn create_systems(world: &World) {
     m0001_spread::create_systems(world);
     m0010_enumeration::create_systems(world);
+    m0011_stack::create_systems(world);
     m0020_explosive_trap::create_systems(world);
+    m0025_gaze::create_systems(world);
+    m0030_jumpable_shockwave::create_systems(world);
+    m0040_tower::create_systems(world);
+    m0050_exaflare::create_systems(world);
+    m0060_rolling_ball::create_systems(world);
+    m0070_persistent_hazard::create_systems(world);
+    m0080_tether::create_systems(world);
+    m0090_chain_lightning::create_systems(world);
+    m0100_donut::create_systems(world);
+    m0110_rotating_sweep::create_systems(world);
+    m0120_bait_puddle::create_systems(world);
     // TEA
     m1000_tea_fire_tornado_1::create_systems(world);
-    m1010_tea_hawk_blaster_tower::create_systems(world);
     m1011_tea_blassty_charge_hit::create_systems(world);
     m1012_tea_limit_cut_end::create_systems(world);
     m1020_tea_spawn_shanoa::create_systems(world);
@@ -114,14 +243,133 @@ pub fn create_observers(world: &World) {
     // Send message to remove VFX objects with IDs
     world
         .observer::<flecs::OnRemove, (&Vfx, &Party)>()
+        .term_at(1)
+        .up()
         .each_iter(|it, _index, (vfx, party)| {
-            if let Some(pc) = find_party_container(&it.world(), &party.id) {
-                let io = get_socket_io(&it.world());
-                pc.each_child(|c| {
-                    c.try_get::<(&Socket, &Player)>(|(s, _)| {
-                        send_stop_vfx(io.clone(), s.id, StopVfxPayload { id: vfx.id });
-                    });
-                });
-            }
+            broadcast_stop_vfx(&it.world(), &party.id, StopVfxPayload { id: vfx.id });
         });
+
+    m0110_rotating_sweep::create_observers(world);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn create_party(world: &World, player_count: u64) -> (EntityView<'_>, Vec<Entity>) {
+        let pc = world.entity().add(PartyContainer);
+        let players = (0..player_count)
+            .map(|i| {
+                *world
+                    .entity()
+                    .child_of(pc)
+                    .set(Player {
+                        content_id: i,
+                        name: format!("Player {i}"),
+                    })
+                    .set(State { is_alive: true })
+            })
+            .collect();
+        (pc, players)
+    }
+
+    fn create_targeting_mechanic<'a>(
+        world: &'a World,
+        pc: EntityView<'a>,
+        targets: &[Entity],
+        policy: Option<TargetLeavePolicy>,
+    ) -> EntityView<'a> {
+        let m = create_generic_mechanic(world, "test".to_string(), 0, *pc)
+            .set(Targets {
+                player_entities: targets.to_vec(),
+            })
+            .set(Affects {
+                player_entities: targets.iter().map(|t| (*t, 1)).collect::<HashMap<_, _>>(),
+            });
+        if let Some(policy) = policy {
+            m.set(OnTargetLeave { policy });
+        }
+        m
+    }
+
+    fn targets_of(m: &EntityView<'_>) -> Vec<Entity> {
+        m.get::<&Targets>(|t| t.player_entities.clone())
+    }
+
+    #[test]
+    fn leaving_target_is_dropped_by_default() {
+        let world = World::new();
+        let (pc, players) = create_party(&world, 3);
+        let m = create_targeting_mechanic(&world, pc, &players[0..2], None);
+
+        remove_player_from_mechanics(&pc, players[0]);
+
+        assert_eq!(targets_of(&m), vec![players[1]]);
+        m.get::<&Affects>(|a| assert!(!a.player_entities.contains_key(&players[0])));
+        assert!(!m.has(Retargeted::id()));
+    }
+
+    #[test]
+    fn leaving_target_is_transferred_to_an_untargeted_player() {
+        let world = World::new();
+        let (pc, players) = create_party(&world, 3);
+        let m = create_targeting_mechanic(
+            &world,
+            pc,
+            &players[0..2],
+            Some(TargetLeavePolicy::Transfer),
+        );
+
+        remove_player_from_mechanics(&pc, players[0]);
+
+        assert_eq!(targets_of(&m), vec![players[2], players[1]]);
+        assert!(m.has(Retargeted::id()));
+    }
+
+    #[test]
+    fn transfer_skips_dead_players_and_drops_without_candidates() {
+        let world = World::new();
+        let (pc, players) = create_party(&world, 2);
+        players[1]
+            .entity_view(&world)
+            .set(State { is_alive: false });
+        let m = create_targeting_mechanic(
+            &world,
+            pc,
+            &players[0..1],
+            Some(TargetLeavePolicy::Transfer),
+        );
+
+        remove_player_from_mechanics(&pc, players[0]);
+
+        assert!(targets_of(&m).is_empty());
+        assert!(!m.has(Retargeted::id()));
+    }
+
+    #[test]
+    fn leaving_target_fails_the_mechanic() {
+        let world = World::new();
+        let (pc, players) = create_party(&world, 2);
+        let m =
+            create_targeting_mechanic(&world, pc, &players[0..1], Some(TargetLeavePolicy::Fail));
+        let id = *m;
+
+        remove_player_from_mechanics(&pc, players[0]);
+
+        assert!(!id.entity_view(&world).is_valid());
+    }
+
+    #[test]
+    fn leaving_non_target_does_not_fail_the_mechanic() {
+        let world = World::new();
+        let (pc, players) = create_party(&world, 2);
+        let m =
+            create_targeting_mechanic(&world, pc, &players[0..1], Some(TargetLeavePolicy::Fail));
+
+        remove_player_from_mechanics(&pc, players[1]);
+
+        assert!(m.is_valid());
+        assert_eq!(targets_of(&m), vec![players[0]]);
+    }
 }
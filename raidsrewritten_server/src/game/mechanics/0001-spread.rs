@@ -2,7 +2,6 @@ use crate::{
     game::{components::*, condition::Condition, utils::*},
     webserver::message::{ApplyConditionPayload, PlayActorVfxOnTargetPayload},
 };
-use distances::vectors::euclidean_sq;
 use flecs_ecs::prelude::*;
 use std::collections::HashMap;
 use tracing::info;
@@ -31,6 +30,8 @@ pub fn create_mechanic(entity: EntityView<'_>) -> EntityView<'_> {
 pub fn create_systems(world: &World) {
     world
         .system::<(&Mechanic, &mut Spread, &Party)>()
+        .term_at(2)
+        .up()
         .each_iter(|it, index, (mechanic, spread, party)| {
             let entity = it.entity(index);
 
@@ -53,22 +54,15 @@ pub fn create_systems(world: &World) {
                 });
 
                 // Send omen vfx
-                if let Some(pc) = find_party_container(&it.world(), &party.id) {
-                    let io = get_socket_io(&it.world());
-                    pc.each_child(|c| {
-                        c.try_get::<&Socket>(|s| {
-                            send_play_actor_vfx_on_target(
-                                io.clone(),
-                                s.id,
-                                PlayActorVfxOnTargetPayload {
-                                    vfx_path: spread.omen_vfx_path.clone(),
-                                    content_id_targets: targets.clone(),
-                                    ..Default::default()
-                                },
-                            );
-                        });
-                    });
-                }
+                broadcast_play_actor_vfx_on_target(
+                    &it.world(),
+                    &party.id,
+                    PlayActorVfxOnTargetPayload {
+                        vfx_path: spread.omen_vfx_path.clone(),
+                        content_id_targets: targets,
+                        ..Default::default()
+                    },
+                );
                 return;
             }
 
@@ -80,6 +74,7 @@ pub fn create_systems(world: &World) {
 
             if !entity.has(Affects::id()) {
                 let mut affects: HashMap<Entity, u8> = HashMap::new();
+                let extent = entity.try_get::<&VerticalExtent>(|v| *v);
 
                 // Snapshot
                 entity.try_get::<&mut Targets>(|t| {
@@ -87,7 +82,9 @@ pub fn create_systems(world: &World) {
                     t.player_entities.retain(|e| {
                         let mut valid_target = false;
                         if let Some(ev) = get_entity_view(e, &it.world()) {
-                            ev.try_get::<&State>(|s| valid_target = s.is_alive);
+                            ev.try_get::<&State>(|s| {
+                                valid_target = s.is_alive && !ev.has(Stale::id())
+                            });
                         }
                         valid_target
                     });
@@ -99,16 +96,15 @@ pub fn create_systems(world: &World) {
                             e1.try_get::<(&Player, &Position)>(|(_, p1)| {
                                 // affect all players within radius
                                 pc.each_child(|c| {
+                                    if c.has(Stale::id()) {
+                                        return;
+                                    }
                                     c.try_get::<(&Player, &Position, &State)>(|(_, p2, s2)| {
                                         if !s2.is_alive {
                                             return;
                                         }
 
-                                        let p1 = [p1.x, p1.z];
-                                        let p2 = [p2.x, p2.z];
-                                        let distance_sq: f32 = euclidean_sq(&p1, &p2);
-
-                                        if distance_sq <= f32::powi(spread.radius, 2) {
+                                        if is_in_circle(p1, spread.radius, extent.as_ref(), p2) {
                                             add_affect(&mut affects, &c, 1);
                                         }
                                     });
@@ -124,22 +120,15 @@ pub fn create_systems(world: &World) {
 
                 // Send attack vfx
                 let targets = get_target_ids(&entity);
-                let io = get_socket_io(&it.world());
-                if let Some(pc) = find_party_container(&it.world(), &party.id) {
-                    pc.each_child(|c| {
-                        c.try_get::<&Socket>(|s| {
-                            send_play_actor_vfx_on_target(
-                                io.clone(),
-                                s.id,
-                                PlayActorVfxOnTargetPayload {
-                                    vfx_path: spread.attack_vfx_path.clone(),
-                                    content_id_targets: targets.clone(),
-                                    ..Default::default()
-                                },
-                            );
-                        });
-                    });
-                }
+                broadcast_play_actor_vfx_on_target(
+                    &it.world(),
+                    &party.id,
+                    PlayActorVfxOnTargetPayload {
+                        vfx_path: spread.attack_vfx_path.clone(),
+                        content_id_targets: targets,
+                        ..Default::default()
+                    },
+                );
             }
 
             spread.effect_delay -= it.delta_time();
@@ -150,7 +139,6 @@ pub fn create_systems(world: &World) {
 
             // Send conditions
             entity.try_get::<&Affects>(|a| {
-                let io = get_socket_io(&it.world());
                 for (e, affect_count) in &a.player_entities {
                     let condition_duration = (affect_count - 1) as f32 * 5.0;
                     if condition_duration > 0.0
@@ -158,7 +146,7 @@ pub fn create_systems(world: &World) {
                     {
                         ev.try_get::<&Socket>(|s| {
                             send_apply_condition(
-                                io.clone(),
+                                &it.world(),
                                 s.id,
                                 ApplyConditionPayload {
                                     condition: Condition::Stun,
@@ -2,7 +2,6 @@ use crate::{
     game::{components::*, condition::Condition, utils::*},
     webserver::message::{ApplyConditionPayload, PlayActorVfxOnTargetPayload},
 };
-use distances::vectors::euclidean_sq;
 use flecs_ecs::prelude::*;
 use rand::seq::IndexedRandom;
 use std::collections::HashMap;
@@ -10,6 +9,7 @@ use tracing::info;
 
 // This enumeration is placed on one random player and does not go off on dead bodies.
 // 2+ players successfully resolve this enumeration.
+// If the target leaves before the snapshot, the enumeration moves to another player.
 
 #[derive(Component, Debug)]
 pub struct Enumeration {
@@ -21,21 +21,27 @@ pub struct Enumeration {
 }
 
 pub fn create_mechanic(entity: EntityView<'_>) -> EntityView<'_> {
-    entity.set(Enumeration {
-        time_to_snapshot: 6.0,
-        effect_delay: 0.2,
-        radius: 3.0,
-        omen_vfx_path: "vfx/lockon/eff/2tagup_3m_6s_x.avfx".to_string(),
-        attack_vfx_paths: [
-            "vfx/monster/gimmick4/eff/z5fb_b_g10c0x.avfx".to_string(),
-            "vfx/monster/gimmick4/eff/z5fb_b_g10c1x.avfx".to_string(),
-        ],
-    })
+    entity
+        .set(OnTargetLeave {
+            policy: TargetLeavePolicy::Transfer,
+        })
+        .set(Enumeration {
+            time_to_snapshot: 6.0,
+            effect_delay: 0.2,
+            radius: 3.0,
+            omen_vfx_path: "vfx/lockon/eff/2tagup_3m_6s_x.avfx".to_string(),
+            attack_vfx_paths: [
+                "vfx/monster/gimmick4/eff/z5fb_b_g10c0x.avfx".to_string(),
+                "vfx/monster/gimmick4/eff/z5fb_b_g10c1x.avfx".to_string(),
+            ],
+        })
 }
 
 pub fn create_systems(world: &World) {
     world
         .system::<(&Mechanic, &mut Enumeration, &Party)>()
+        .term_at(2)
+        .up()
         .each_iter(|it, index, (mechanic, enumeration, party)| {
             let entity = it.entity(index);
 
@@ -67,25 +73,34 @@ pub fn create_systems(world: &World) {
                 });
 
                 // Send omen vfx
-                if let Some(pc) = find_party_container(&it.world(), &party.id) {
-                    let io = get_socket_io(&it.world());
-                    pc.each_child(|c| {
-                        c.try_get::<&Socket>(|s| {
-                            send_play_actor_vfx_on_target(
-                                io.clone(),
-                                s.id,
-                                PlayActorVfxOnTargetPayload {
-                                    vfx_path: enumeration.omen_vfx_path.clone(),
-                                    content_id_targets: targets.clone(),
-                                    ..Default::default()
-                                },
-                            );
-                        });
-                    });
-                }
+                broadcast_play_actor_vfx_on_target(
+                    &it.world(),
+                    &party.id,
+                    PlayActorVfxOnTargetPayload {
+                        vfx_path: enumeration.omen_vfx_path.clone(),
+                        content_id_targets: targets,
+                        ..Default::default()
+                    },
+                );
                 return;
             }
 
+            if entity.has(Retargeted::id()) {
+                entity.remove(Retargeted::id());
+                if !entity.has(Affects::id()) {
+                    // Move the omen vfx to the new target
+                    broadcast_play_actor_vfx_on_target(
+                        &it.world(),
+                        &party.id,
+                        PlayActorVfxOnTargetPayload {
+                            vfx_path: enumeration.omen_vfx_path.clone(),
+                            content_id_targets: get_target_ids(&entity),
+                            ..Default::default()
+                        },
+                    );
+                }
+            }
+
             enumeration.time_to_snapshot -= it.delta_time();
 
             if enumeration.time_to_snapshot > 0.0 {
@@ -94,6 +109,7 @@ pub fn create_systems(world: &World) {
 
             if !entity.has(Affects::id()) {
                 let mut affects: HashMap<Entity, u8> = HashMap::new();
+                let extent = entity.try_get::<&VerticalExtent>(|v| *v);
 
                 // Snapshot
                 entity.try_get::<&mut Targets>(|t| {
@@ -101,7 +117,9 @@ pub fn create_systems(world: &World) {
                     t.player_entities.retain(|e| {
                         let mut valid_target = false;
                         if let Some(ev) = get_entity_view(e, &it.world()) {
-                            ev.try_get::<&State>(|s| valid_target = s.is_alive);
+                            ev.try_get::<&State>(|s| {
+                                valid_target = s.is_alive && !ev.has(Stale::id())
+                            });
                         }
                         valid_target
                     });
@@ -114,16 +132,16 @@ pub fn create_systems(world: &World) {
                                 // affect all players within radius
                                 let mut enumeration_success = false;
                                 pc.each_child(|c| {
+                                    if c.has(Stale::id()) {
+                                        return;
+                                    }
                                     c.try_get::<(&Player, &Position, &State)>(|(pl2, p2, s2)| {
                                         if !s2.is_alive {
                                             return;
                                         }
 
-                                        let p1 = [p1.x, p1.z];
-                                        let p2 = [p2.x, p2.z];
-                                        let distance_sq: f32 = euclidean_sq(&p1, &p2);
-
-                                        if distance_sq <= f32::powi(enumeration.radius, 2) {
+                                        if is_in_circle(p1, enumeration.radius, extent.as_ref(), p2)
+                                        {
                                             if pl1.content_id != pl2.content_id {
                                                 enumeration_success = true;
                                             }
@@ -146,23 +164,16 @@ pub fn create_systems(world: &World) {
 
                 // Send attack vfx
                 let targets = get_target_ids(&entity);
-                let io = get_socket_io(&it.world());
-                if let Some(pc) = find_party_container(&it.world(), &party.id) {
-                    pc.each_child(|c| {
-                        c.try_get::<&Socket>(|s| {
-                            for vfx in &enumeration.attack_vfx_paths {
-                                send_play_actor_vfx_on_target(
-                                    io.clone(),
-                                    s.id,
-                                    PlayActorVfxOnTargetPayload {
-                                        vfx_path: vfx.clone(),
-                                        content_id_targets: targets.clone(),
-                                        ..Default::default()
-                                    },
-                                );
-                            }
-                        });
-                    });
+                for vfx in &enumeration.attack_vfx_paths {
+                    broadcast_play_actor_vfx_on_target(
+                        &it.world(),
+                        &party.id,
+                        PlayActorVfxOnTargetPayload {
+                            vfx_path: vfx.clone(),
+                            content_id_targets: targets.clone(),
+                            ..Default::default()
+                        },
+                    );
                 }
             }
 
@@ -174,7 +185,6 @@ pub fn create_systems(world: &World) {
 
             // Send conditions
             entity.try_get::<&Affects>(|a| {
-                let io = get_socket_io(&it.world());
                 for (e, affect_count) in &a.player_entities {
                     let condition_duration = (affect_count - 1) as f32 * 5.0;
                     if condition_duration > 0.0
@@ -182,7 +192,7 @@ pub fn create_systems(world: &World) {
                     {
                         ev.try_get::<&Socket>(|s| {
                             send_apply_condition(
-                                io.clone(),
+                                &it.world(),
                                 s.id,
                                 ApplyConditionPayload {
                                     condition: Condition::Stun,
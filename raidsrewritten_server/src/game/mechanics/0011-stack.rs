@@ -0,0 +1,269 @@
+use crate::{
+    game::{components::*, condition::Condition, role, utils::*},
+    webserver::message::{ApplyConditionPayload, PlayActorVfxOnTargetPayload},
+};
+use distances::vectors::euclidean_sq;
+use flecs_ecs::prelude::*;
+use rand::seq::IndexedRandom;
+use std::collections::HashMap;
+use tracing::info;
+
+// A stack placed on random living players, which does not go off on dead bodies.
+// Each stack has to be shared by between min_soakers and max_soakers players, counting the target.
+// If too few players share it, all of them are hit. If too many do, the ones furthest from the target are hit.
+// Players in the stack who aren't allowed to soak it are always hit.
+//
+// Configured through the extra data as "target_count,radius,min_soakers,max_soakers,target_roles,soaker_roles",
+// where the roles are a list of role numbers (e.g. "23" for healers and dps), and empty means any role.
+// Any missing or invalid value is left at its default.
+
+#[derive(Component, Debug)]
+pub struct Stack {
+    target_count: usize,
+    radius: f32,
+    min_soakers: usize,
+    max_soakers: usize,
+    target_roles: Vec<role::Role>,
+    soaker_roles: Vec<role::Role>,
+    time_to_snapshot: f32,
+    effect_delay: f32,
+    stun_duration: f32,
+    omen_vfx_path: String,
+    attack_vfx_path: String,
+}
+
+pub fn create_mechanic(entity: EntityView<'_>) -> EntityView<'_> {
+    let mut stack = Stack {
+        target_count: 1,
+        radius: 6.0,
+        min_soakers: 2,
+        max_soakers: 8,
+        target_roles: Vec::new(),
+        soaker_roles: Vec::new(),
+        time_to_snapshot: 5.0,
+        effect_delay: 0.2,
+        stun_duration: 5.0,
+        omen_vfx_path: "vfx/lockon/eff/com_share1f.avfx".to_string(),
+        attack_vfx_path: "vfx/monster/gimmick3/eff/n4gb_b_g04c0a1.avfx".to_string(),
+    };
+
+    entity.try_get::<&ExtraMechanicData>(|ed| {
+        let values: Vec<&str> = ed.value.split(',').map(str::trim).collect();
+        if let Some(Ok(n)) = values.first().map(|v| v.parse()) {
+            stack.target_count = n;
+        }
+        if let Some(Ok(r)) = values.get(1).map(|v| v.parse::<f32>())
+            && r.is_finite()
+            && r > 0.0
+        {
+            stack.radius = r;
+        }
+        if let Some(Ok(n)) = values.get(2).map(|v| v.parse()) {
+            stack.min_soakers = n;
+        }
+        if let Some(Ok(n)) = values.get(3).map(|v| v.parse()) {
+            stack.max_soakers = n;
+        }
+        if let Some(v) = values.get(4) {
+            stack.target_roles = parse_roles(v);
+        }
+        if let Some(v) = values.get(5) {
+            stack.soaker_roles = parse_roles(v);
+        }
+    });
+    stack.max_soakers = usize::max(stack.max_soakers, stack.min_soakers);
+
+    entity.set(stack)
+}
+
+fn parse_roles(value: &str) -> Vec<role::Role> {
+    value
+        .chars()
+        .filter_map(|c| match c {
+            '1' => Some(role::Role::Tank),
+            '2' => Some(role::Role::Healer),
+            '3' => Some(role::Role::Dps),
+            _ => None,
+        })
+        .collect()
+}
+
+fn has_role(player: &EntityView<'_>, roles: &[role::Role]) -> bool {
+    roles.is_empty()
+        || player
+            .try_get::<&Role>(|r| roles.contains(&r.role))
+            .unwrap_or(false)
+}
+
+pub fn create_systems(world: &World) {
+    world
+        .system::<(&Mechanic, &mut Stack, &Party)>()
+        .term_at(2)
+        .up()
+        .each_iter(|it, index, (mechanic, stack, party)| {
+            let entity = it.entity(index);
+
+            if !entity.has(Targets::id()) {
+                // Assign targets
+                let mut target_players: Vec<Entity> = Vec::new();
+                let mut targets: Vec<u64> = Vec::new();
+
+                if let Some(pc) = find_party_container(&it.world(), &party.id) {
+                    let mut players: Vec<Entity> = Vec::new();
+                    pc.each_child(|c| {
+                        if c.has(Stale::id()) || !has_role(&c, &stack.target_roles) {
+                            return;
+                        }
+                        c.try_get::<(&Player, &State)>(|(_, s)| {
+                            if s.is_alive {
+                                players.push(*c);
+                            }
+                        });
+                    });
+
+                    target_players
+                        .extend(players.choose_multiple(&mut rand::rng(), stack.target_count));
+                    for target in &target_players {
+                        target.entity_view(it.world()).try_get::<&Player>(|p| {
+                            targets.push(p.content_id);
+                        });
+                    }
+                }
+
+                entity.set(Targets {
+                    player_entities: target_players,
+                });
+
+                // Send omen vfx
+                broadcast_play_actor_vfx_on_target(
+                    &it.world(),
+                    &party.id,
+                    PlayActorVfxOnTargetPayload {
+                        vfx_path: stack.omen_vfx_path.clone(),
+                        content_id_targets: targets,
+                        ..Default::default()
+                    },
+                );
+                return;
+            }
+
+            stack.time_to_snapshot -= it.delta_time();
+
+            if stack.time_to_snapshot > 0.0 {
+                return;
+            }
+
+            if !entity.has(Affects::id()) {
+                let mut affects: HashMap<Entity, u8> = HashMap::new();
+                let extent = entity.try_get::<&VerticalExtent>(|v| *v);
+
+                // Snapshot
+                entity.try_get::<&mut Targets>(|t| {
+                    // Prune valid targets
+                    t.player_entities.retain(|e| {
+                        let mut valid_target = false;
+                        if let Some(ev) = get_entity_view(e, &it.world()) {
+                            ev.try_get::<&State>(|s| {
+                                valid_target = s.is_alive && !ev.has(Stale::id())
+                            });
+                        }
+                        valid_target
+                    });
+
+                    if let Some(pc) = find_party_container(&it.world(), &party.id) {
+                        for e in &t.player_entities {
+                            // For every target player,
+                            let e1 = e.entity_view(it.world());
+                            e1.try_get::<(&Player, &Position)>(|(_, p1)| {
+                                // find all players within radius
+                                let mut soakers: Vec<(Entity, f32)> = Vec::new();
+                                pc.each_child(|c| {
+                                    if c.has(Stale::id()) {
+                                        return;
+                                    }
+                                    c.try_get::<(&Player, &Position, &State)>(|(_, p2, s2)| {
+                                        if !s2.is_alive
+                                            || !is_in_circle(p1, stack.radius, extent.as_ref(), p2)
+                                        {
+                                            return;
+                                        }
+
+                                        if *c == *e {
+                                            soakers.push((*c, 0.0));
+                                        } else if has_role(&c, &stack.soaker_roles) {
+                                            soakers.push((
+                                                *c,
+                                                euclidean_sq(&[p1.x, p1.z], &[p2.x, p2.z]),
+                                            ));
+                                        } else {
+                                            add_affect(&mut affects, &c, 1);
+                                        }
+                                    });
+                                });
+
+                                if soakers.len() < stack.min_soakers {
+                                    // Under-soaked
+                                    for (s, _) in &soakers {
+                                        add_affect(&mut affects, s, 1);
+                                    }
+                                } else if soakers.len() > stack.max_soakers {
+                                    // Over-soaked, the target is always closest
+                                    soakers.sort_by(|(_, d1), (_, d2)| d1.total_cmp(d2));
+                                    for (s, _) in &soakers[stack.max_soakers..] {
+                                        add_affect(&mut affects, s, 1);
+                                    }
+                                }
+                            });
+                        }
+                    }
+                });
+
+                entity.set(Affects {
+                    player_entities: affects,
+                });
+
+                // Send attack vfx
+                let targets = get_target_ids(&entity);
+                broadcast_play_actor_vfx_on_target(
+                    &it.world(),
+                    &party.id,
+                    PlayActorVfxOnTargetPayload {
+                        vfx_path: stack.attack_vfx_path.clone(),
+                        content_id_targets: targets,
+                        ..Default::default()
+                    },
+                );
+            }
+
+            stack.effect_delay -= it.delta_time();
+
+            if stack.effect_delay > 0.0 {
+                return;
+            }
+
+            // Send conditions
+            entity.try_get::<&Affects>(|a| {
+                for (e, affect_count) in &a.player_entities {
+                    if let Some(ev) = get_entity_view(e, &it.world()) {
+                        ev.try_get::<&Socket>(|s| {
+                            send_apply_condition(
+                                &it.world(),
+                                s.id,
+                                ApplyConditionPayload {
+                                    condition: Condition::Stun,
+                                    duration: *affect_count as f32 * stack.stun_duration,
+                                    ..Default::default()
+                                },
+                            );
+                        });
+                    }
+                }
+            });
+
+            info!(
+                mechanic.request_id,
+                mechanic.mechanic_id, party.id, "Completing Mechanic"
+            );
+            entity.remove(Stack::id());
+        });
+}
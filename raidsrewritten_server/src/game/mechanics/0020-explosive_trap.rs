@@ -1,8 +1,7 @@
 use crate::{
-    game::{components::*, condition::Condition, utils::*},
+    game::{components::*, condition::Condition, movement::get_extrapolated_position, utils::*},
     webserver::message::*,
 };
-use distances::vectors::euclidean_sq;
 use flecs_ecs::prelude::*;
 use std::{
     collections::HashMap,
@@ -49,6 +48,8 @@ pub fn create_mechanic(entity: EntityView<'_>) -> EntityView<'_> {
 pub fn create_systems(world: &World) {
     world
         .system::<(&Mechanic, &mut Trap, &Position, &Rotation, &Party)>()
+        .term_at(4)
+        .up()
         .each_iter(|it, index, (mechanic, trap, position, rotation, party)| {
             let entity = it.entity(index);
 
@@ -68,27 +69,20 @@ pub fn create_systems(world: &World) {
                     let vfx_id = Uuid::new_v4().as_u128();
                     entity.set(Vfx { id: vfx_id });
 
-                    if let Some(pc) = find_party_container(&it.world(), &party.id) {
-                        let io = get_socket_io(&it.world());
-                        pc.each_child(|c| {
-                            c.try_get::<(&Socket, &Player)>(|(s, _)| {
-                                send_play_static_vfx(
-                                    io.clone(),
-                                    s.id,
-                                    PlayStaticVfxPayload {
-                                        id: vfx_id,
-                                        vfx_path: trap.omen_vfx_path.clone(),
-                                        is_omen: true,
-                                        world_position_x: position.x,
-                                        world_position_y: position.y,
-                                        world_position_z: position.z,
-                                        rotation: rotation.value,
-                                        ..Default::default()
-                                    },
-                                );
-                            });
-                        });
-                    }
+                    broadcast_play_static_vfx(
+                        &it.world(),
+                        &party.id,
+                        PlayStaticVfxPayload {
+                            id: vfx_id,
+                            vfx_path: trap.omen_vfx_path.clone(),
+                            is_omen: true,
+                            world_position_x: position.x,
+                            world_position_y: position.y,
+                            world_position_z: position.z,
+                            rotation: rotation.value,
+                            ..Default::default()
+                        },
+                    );
                 }
 
                 // Activation check procedure
@@ -104,53 +98,55 @@ pub fn create_systems(world: &World) {
                 trap.time_to_next_activation_check += trap.activation_check_interval;
 
                 // Actual activation check
-                let pos1 = [position.x, position.z];
+                let extent = entity.try_get::<&VerticalExtent>(|v| *v);
                 if let Some(pc) = find_party_container(&it.world(), &party.id) {
                     pc.each_child(|c| {
-                        if trap.activated {
+                        if trap.activated || c.has(Stale::id()) || !c.has(Player::id()) {
                             return;
                         }
-                        c.try_get::<(&Player, &Position)>(|(_, pos)| {
-                            let pos2 = [pos.x, pos.z];
-                            let distance_sq: f32 = euclidean_sq(&pos1, &pos2);
-
-                            if distance_sq <= f32::powi(trap.activation_radius, 2) {
-                                trap.activated = true;
-                            }
-                        });
+                        // Checks only happen every activation_check_interval, so use where the player is
+                        // estimated to be now rather than where they last reported
+                        if let Some(pos) = get_extrapolated_position(&c, 0.0)
+                            && is_in_circle(position, trap.activation_radius, extent.as_ref(), &pos)
+                        {
+                            trap.activated = true;
+                        }
                     });
 
                     // Get affected
                     if trap.activated {
                         let mut affects: HashMap<Entity, u8> = HashMap::new();
-                        let io = get_socket_io(&it.world());
                         pc.each_child(|c| {
-                            c.try_get::<(&Socket, &Player, &Position)>(|(s, _, pos)| {
-                                let pos2 = [pos.x, pos.z];
-                                let distance_sq: f32 = euclidean_sq(&pos1, &pos2);
-
-                                if distance_sq <= f32::powi(trap.effect_radius, 2) {
-                                    add_affect(&mut affects, &c, 1);
-                                }
-
-                                // Stop trap vfx
-                                entity.remove(Vfx::id());
-
-                                // Play explosion vfx
-                                send_play_actor_vfx_on_position(
-                                    io.clone(),
-                                    s.id,
-                                    PlayActorVfxOnPositionPayload {
-                                        vfx_path: trap.attack_vfx_path.clone(),
-                                        world_position_x: position.x,
-                                        world_position_y: position.y,
-                                        world_position_z: position.z,
-                                        rotation: rotation.value,
-                                    },
-                                );
-                            });
+                            if c.has(Stale::id()) {
+                                return;
+                            }
+                            if !c.has(Player::id()) {
+                                return;
+                            }
+                            // Use the same estimate as the activation check, so whoever set it off is hit
+                            if let Some(pos) = get_extrapolated_position(&c, 0.0)
+                                && is_in_circle(position, trap.effect_radius, extent.as_ref(), &pos)
+                            {
+                                add_affect(&mut affects, &c, 1);
+                            }
                         });
 
+                        // Stop trap vfx
+                        entity.remove(Vfx::id());
+
+                        // Play explosion vfx
+                        broadcast_play_actor_vfx_on_position(
+                            &it.world(),
+                            &party.id,
+                            PlayActorVfxOnPositionPayload {
+                                vfx_path: trap.attack_vfx_path.clone(),
+                                world_position_x: position.x,
+                                world_position_y: position.y,
+                                world_position_z: position.z,
+                                rotation: rotation.value,
+                            },
+                        );
+
                         entity.set(Affects {
                             player_entities: affects,
                         });
@@ -166,12 +162,11 @@ pub fn create_systems(world: &World) {
 
             // Send effects
             entity.try_get::<&Affects>(|a| {
-                let io = get_socket_io(&it.world());
                 for e in a.player_entities.keys() {
                     if let Some(ev) = get_entity_view(e, &it.world()) {
                         ev.try_get::<&Socket>(|s| {
                             send_apply_condition(
-                                io.clone(),
+                                &it.world(),
                                 s.id,
                                 ApplyConditionPayload {
                                     condition: Condition::Stun,
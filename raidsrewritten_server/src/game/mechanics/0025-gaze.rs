@@ -0,0 +1,91 @@
+use crate::{
+    game::{
+        components::*,
+        condition::{self, apply_condition},
+        utils::*,
+    },
+    webserver::message::PlayActorVfxOnPositionPayload,
+};
+use flecs_ecs::prelude::*;
+use tracing::info;
+
+// A gaze ("look away") from the mechanic's position. Players facing it when it snapshots are petrified,
+// which is represented with a stun. Players who don't report a facing direction can't be judged, and are not hit.
+
+#[derive(Component, Debug)]
+pub struct Gaze {
+    time_to_snapshot: f32,
+    max_angle_degrees: f32,
+    stun_duration: f32,
+    omen_vfx_path: String,
+    omen_sent: bool,
+}
+
+pub fn create_mechanic(entity: EntityView<'_>) -> EntityView<'_> {
+    entity.set(Gaze {
+        time_to_snapshot: 5.0,
+        max_angle_degrees: 45.0,
+        stun_duration: 10.0,
+        omen_vfx_path: "vfx/common/eff/cmma_shoot1c.avfx".to_string(),
+        omen_sent: false,
+    })
+}
+
+pub fn create_systems(world: &World) {
+    world
+        .system::<(&Mechanic, &mut Gaze, &Position, &Party)>()
+        .term_at(3)
+        .up()
+        .each_iter(|it, index, (mechanic, gaze, position, party)| {
+            let entity = it.entity(index);
+            let world = &it.world();
+
+            if !gaze.omen_sent {
+                broadcast_play_actor_vfx_on_position(
+                    world,
+                    &party.id,
+                    PlayActorVfxOnPositionPayload {
+                        vfx_path: gaze.omen_vfx_path.clone(),
+                        world_position_x: position.x,
+                        world_position_y: position.y,
+                        world_position_z: position.z,
+                        rotation: 0.0,
+                    },
+                );
+                gaze.omen_sent = true;
+                return;
+            }
+
+            gaze.time_to_snapshot -= it.delta_time();
+            if gaze.time_to_snapshot > 0.0 {
+                return;
+            }
+
+            // Snapshot
+            if let Some(pc) = find_party_container(world, &party.id) {
+                pc.each_child(|c| {
+                    if c.has(Stale::id()) {
+                        return;
+                    }
+                    c.try_get::<(&Player, &State)>(|(_, s)| {
+                        if !s.is_alive || !is_entity_facing(&c, position, gaze.max_angle_degrees) {
+                            return;
+                        }
+                        apply_condition(
+                            &c,
+                            condition::Condition::Stun as u128,
+                            condition::Condition::Stun,
+                            gaze.stun_duration,
+                            false,
+                        );
+                    });
+                });
+            }
+
+            info!(
+                mechanic.request_id,
+                mechanic.mechanic_id, party.id, "Completing Mechanic"
+            );
+            entity.remove(Gaze::id());
+        });
+}
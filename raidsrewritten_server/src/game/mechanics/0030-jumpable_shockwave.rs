@@ -0,0 +1,183 @@
+use crate::{
+    game::{
+        components::*,
+        condition::{self, apply_condition},
+        movement::{RESOLVE_DELAY, get_position_at, had_movement_flag_between},
+        utils::*,
+    },
+    webserver::message::PlayActorVfxOnPositionPayload,
+};
+use flecs_ecs::prelude::*;
+use std::{
+    collections::HashMap,
+    f32::consts::PI,
+    time::{Duration, Instant},
+};
+use tracing::info;
+
+// A ring expanding from the mechanic's position, which players have to jump over.
+// When the ring passes under a living player, they're stunned unless they were airborne at that moment,
+// either by reporting a jump or by standing above the shockwave's VerticalExtent.
+
+// Players at most this far above the shockwave get hit, unless StartMechanic gave a VerticalExtent
+const JUMP_CLEARANCE: f32 = 1.0;
+// How far apart a reported jump and the ring passing can be, and still count as jumping over it
+const AIRBORNE_TOLERANCE: Duration = Duration::from_millis(150);
+// The vfx is played below the mechanic's position so that the ring lines up with the ground
+const VFX_Y_OFFSET: f32 = -4.7;
+
+#[derive(Component, Debug)]
+pub struct JumpableShockwave {
+    radius: f32,
+    max_radius: f32,
+    speed: f32,
+    stun_duration: f32,
+    vfx_path: String,
+    vfx_sent: bool,
+    // Whether each player was inside the ring as of the last tick
+    players_inside: HashMap<Entity, bool>,
+    // Players the ring passed under, and when
+    pending_passes: Vec<(Entity, Instant)>,
+}
+
+pub fn create_mechanic(entity: EntityView<'_>) -> EntityView<'_> {
+    if !entity.has(VerticalExtent::id()) {
+        entity.set(VerticalExtent {
+            below: f32::INFINITY,
+            above: JUMP_CLEARANCE,
+        });
+    }
+    entity.set(JumpableShockwave {
+        radius: 1.5,
+        max_radius: 30.0,
+        speed: 4.0,
+        stun_duration: 10.0,
+        vfx_path: "vfx/monster/gimmick3/eff/n4r2_b1_g4c0w.avfx".to_string(),
+        vfx_sent: false,
+        players_inside: HashMap::new(),
+        pending_passes: Vec::new(),
+    })
+}
+
+pub fn create_systems(world: &World) {
+    world
+        .system::<(
+            &Mechanic,
+            &mut JumpableShockwave,
+            &Position,
+            &Rotation,
+            &Party,
+        )>()
+        .term_at(4)
+        .up()
+        .each_iter(
+            |it, index, (mechanic, shockwave, position, rotation, party)| {
+                let entity = it.entity(index);
+                let world = &it.world();
+
+                if !shockwave.vfx_sent {
+                    // The ring vfx only covers half a circle, so it's played twice facing opposite ways
+                    for r in [rotation.value, rotation.value + PI] {
+                        broadcast_play_actor_vfx_on_position(
+                            world,
+                            &party.id,
+                            PlayActorVfxOnPositionPayload {
+                                vfx_path: shockwave.vfx_path.clone(),
+                                world_position_x: position.x,
+                                world_position_y: position.y + VFX_Y_OFFSET,
+                                world_position_z: position.z,
+                                rotation: (r + PI).rem_euclid(2.0 * PI) - PI,
+                            },
+                        );
+                    }
+                    shockwave.vfx_sent = true;
+                    return;
+                }
+
+                let now = Instant::now();
+                let extent = entity.try_get::<&VerticalExtent>(|v| *v);
+
+                // Resolve passes
+                shockwave.pending_passes.retain(|(e, passed_at)| {
+                    if now.duration_since(*passed_at) < RESOLVE_DELAY {
+                        return true;
+                    }
+                    let Some(player) = get_entity_view(e, world) else {
+                        return false;
+                    };
+                    player.try_get::<(&Player, &State)>(|(pl, s)| {
+                        if !s.is_alive {
+                            return;
+                        }
+                        let jumped = had_movement_flag_between(
+                            &player,
+                            MovementFlags::JUMPING,
+                            passed_at
+                                .checked_sub(AIRBORNE_TOLERANCE)
+                                .unwrap_or(*passed_at),
+                            *passed_at + AIRBORNE_TOLERANCE,
+                        );
+                        // Where the player was when the ring passed, not where they are now
+                        let above = get_position_at(&player, *passed_at).is_some_and(|p| {
+                            !is_within_vertical_extent(position.y, extent.as_ref(), p.y)
+                        });
+                        info!(
+                            mechanic.request_id,
+                            pl.content_id, jumped, above, "Shockwave passed player"
+                        );
+                        if !jumped && !above {
+                            apply_condition(
+                                &player,
+                                condition::Condition::Stun as u128,
+                                condition::Condition::Stun,
+                                shockwave.stun_duration,
+                                false,
+                            );
+                        }
+                    });
+                    false
+                });
+
+                if shockwave.radius >= shockwave.max_radius {
+                    if shockwave.pending_passes.is_empty() {
+                        info!(
+                            mechanic.request_id,
+                            mechanic.mechanic_id, party.id, "Completing Mechanic"
+                        );
+                        entity.remove(JumpableShockwave::id());
+                    }
+                    return;
+                }
+
+                shockwave.radius = f32::min(
+                    shockwave.radius + shockwave.speed * it.delta_time(),
+                    shockwave.max_radius,
+                );
+
+                // Detect the ring passing players, in either direction
+                if let Some(pc) = find_party_container(world, &party.id) {
+                    pc.each_child(|c| {
+                        if c.has(Stale::id()) {
+                            return;
+                        }
+                        c.try_get::<(&Player, &Position)>(|(_, p)| {
+                            let distance =
+                                ((p.x - position.x).powi(2) + (p.z - position.z).powi(2)).sqrt();
+                            let inside = distance < shockwave.radius;
+                            if let Some(was_inside) = shockwave.players_inside.insert(*c, inside)
+                                && was_inside != inside
+                            {
+                                // Estimate when during this tick the ring reached the player
+                                let since_pass = ((shockwave.radius - distance) / shockwave.speed)
+                                    .clamp(0.0, it.delta_time());
+                                let passed_at = now
+                                    .checked_sub(Duration::from_secs_f32(since_pass))
+                                    .unwrap_or(now);
+                                shockwave.pending_passes.push((*c, passed_at));
+                            }
+                        });
+                    });
+                }
+            },
+        );
+}
@@ -0,0 +1,323 @@
+use crate::{
+    game::{
+        components::*,
+        condition::{self, apply_condition},
+        role,
+        utils::*,
+    },
+    webserver::message::*,
+};
+use flecs_ecs::prelude::*;
+use std::collections::HashMap;
+use tracing::info;
+use uuid::Uuid;
+
+// A tower that needs between min_soakers and max_soakers living players of the allowed roles standing in it.
+// Every player in the tower when it snapshots is a soaker, and gets the on_soak outcome.
+// If the tower isn't soaked by the right players, every living player in the party gets the on_failure outcome.
+//
+// Configured through the extra data as "radius,min_soakers,max_soakers,allowed_roles,tower_vfx",
+// where the roles are a list of role numbers (e.g. "23" for healers and dps), and empty means any role.
+// Any missing or invalid value is left at its default.
+
+// Applied to each soaker along with how many times they were hit, which is 2 if they had the vulnerability
+pub type SoakOutcomeFn = fn(player: &EntityView<'_>, affect_count: u8);
+// Applied to each living player in the party
+pub type FailureOutcomeFn = fn(player: &EntityView<'_>);
+
+#[derive(Component, Debug)]
+pub struct Tower {
+    // Settings
+    pub time_to_snapshot: f32,
+    pub attack_delay: f32,
+    pub effect_delay: f32,
+    pub failure_attack_delay: f32,
+    pub failure_effect_delay: f32,
+    pub radius: f32,
+    pub min_soakers: usize,
+    pub max_soakers: usize,
+    pub allowed_roles: Vec<role::Role>,
+    // Soakers with this condition are hit twice
+    pub vulnerability: Option<condition::Condition>,
+    pub tower_vfx: String,
+    pub attack_vfx: String,
+    pub failure_attack_vfx: String,
+    pub on_soak: Option<SoakOutcomeFn>,
+    pub on_failure: Option<FailureOutcomeFn>,
+    // Runtime
+    phase: Phase,
+    failed: bool,
+}
+
+#[derive(Debug)]
+enum Phase {
+    Omen,
+    Snapshot,
+    Attack,
+    Failure,
+    FailureAttack,
+}
+
+impl Default for Tower {
+    fn default() -> Self {
+        Self {
+            time_to_snapshot: 5.0,
+            attack_delay: 0.2,
+            effect_delay: 0.2,
+            failure_attack_delay: 1.0,
+            failure_effect_delay: 0.1,
+            radius: 3.0,
+            min_soakers: 1,
+            max_soakers: 1,
+            allowed_roles: Vec::new(),
+            vulnerability: None,
+            tower_vfx: "vfx/omen/eff/general_trap_o2x.avfx".to_string(),
+            attack_vfx: "vfx/monster/gimmick2/eff/d2ac2_b4_g01c0c.avfx".to_string(),
+            failure_attack_vfx: "vfx/monster/d1025/eff/d1025_sp12_bunsan_zentai_t0s.avfx"
+                .to_string(),
+            on_soak: None,
+            on_failure: Some(stun_on_failure),
+            phase: Phase::Omen,
+            failed: false,
+        }
+    }
+}
+
+fn stun_on_failure(player: &EntityView<'_>) {
+    apply_condition(
+        player,
+        condition::Condition::Stun as u128,
+        condition::Condition::Stun,
+        10.0,
+        false,
+    );
+}
+
+pub fn create_mechanic(entity: EntityView<'_>) -> EntityView<'_> {
+    let mut tower = Tower::default();
+
+    entity.try_get::<&ExtraMechanicData>(|ed| {
+        let values: Vec<&str> = ed.value.split(',').map(str::trim).collect();
+        if let Some(Ok(r)) = values.first().map(|v| v.parse::<f32>())
+            && r.is_finite()
+            && r > 0.0
+        {
+            tower.radius = r;
+        }
+        if let Some(Ok(n)) = values.get(1).map(|v| v.parse()) {
+            tower.min_soakers = n;
+        }
+        if let Some(Ok(n)) = values.get(2).map(|v| v.parse()) {
+            tower.max_soakers = n;
+        }
+        if let Some(v) = values.get(3) {
+            tower.allowed_roles = v
+                .chars()
+                .filter_map(|c| match c {
+                    '1' => Some(role::Role::Tank),
+                    '2' => Some(role::Role::Healer),
+                    '3' => Some(role::Role::Dps),
+                    _ => None,
+                })
+                .collect();
+        }
+        if let Some(v) = values.get(4)
+            && !v.is_empty()
+        {
+            tower.tower_vfx = v.to_string();
+        }
+    });
+    tower.max_soakers = usize::max(tower.max_soakers, tower.min_soakers);
+
+    entity.set(tower)
+}
+
+pub fn create_systems(world: &World) {
+    world
+        .system::<(&Mechanic, &mut Tower, &Position, &Rotation, &Party)>()
+        .term_at(4)
+        .up()
+        .each_iter(|it, index, (mechanic, tower, position, rotation, party)| {
+            let entity = it.entity(index);
+            let world = &it.world();
+
+            match tower.phase {
+                Phase::Omen => {
+                    // Send all players the tower vfx
+                    if !entity.has(Vfx::id()) {
+                        let vfx_id = Uuid::new_v4().as_u128();
+                        entity.set(Vfx { id: vfx_id });
+
+                        broadcast_play_static_vfx(
+                            world,
+                            &party.id,
+                            PlayStaticVfxPayload {
+                                id: vfx_id,
+                                vfx_path: tower.tower_vfx.clone(),
+                                is_omen: true,
+                                world_position_x: position.x,
+                                world_position_y: position.y,
+                                world_position_z: position.z,
+                                rotation: rotation.value,
+                                scale_x: Some(tower.radius),
+                                scale_y: Some(tower.radius),
+                                scale_z: Some(tower.radius),
+                            },
+                        );
+                    }
+
+                    tower.time_to_snapshot -= it.delta_time();
+                    if tower.time_to_snapshot > 0.0 {
+                        return;
+                    }
+
+                    // Snapshot
+                    entity.remove(Vfx::id());
+
+                    let mut affects: HashMap<Entity, u8> = HashMap::new();
+                    let mut soaker_count = 0;
+                    let extent = entity.try_get::<&VerticalExtent>(|v| *v);
+
+                    if let Some(pc) = find_party_container(world, &party.id) {
+                        pc.each_child(|c1| {
+                            if c1.has(Stale::id()) {
+                                return;
+                            }
+                            c1.try_get::<(&Player, &Position, &State)>(|(_, p, s)| {
+                                if !s.is_alive
+                                    || !is_in_circle(position, tower.radius, extent.as_ref(), p)
+                                {
+                                    return;
+                                }
+
+                                let has_allowed_role = tower.allowed_roles.is_empty()
+                                    || c1
+                                        .try_get::<&Role>(|r| tower.allowed_roles.contains(&r.role))
+                                        .unwrap_or(false);
+                                if has_allowed_role {
+                                    soaker_count += 1;
+                                }
+
+                                let mut has_vuln = false;
+                                if let Some(vulnerability) = tower.vulnerability {
+                                    c1.each_child(|c2| {
+                                        c2.try_get::<&Condition>(|condition| {
+                                            if condition.condition == vulnerability {
+                                                has_vuln = true;
+                                            }
+                                        });
+                                    });
+                                }
+                                add_affect(&mut affects, &c1, if has_vuln { 2 } else { 1 });
+                            });
+                        });
+                    }
+
+                    info!(
+                        mechanic.request_id,
+                        soaker_count, tower.min_soakers, tower.max_soakers, "Tower snapshot"
+                    );
+                    tower.failed =
+                        soaker_count < tower.min_soakers || soaker_count > tower.max_soakers;
+
+                    entity.set(Affects {
+                        player_entities: affects,
+                    });
+
+                    tower.phase = Phase::Snapshot;
+                    return;
+                }
+
+                Phase::Snapshot => {
+                    tower.attack_delay -= it.delta_time();
+                    if tower.attack_delay > 0.0 {
+                        return;
+                    }
+
+                    broadcast_play_actor_vfx_on_position(
+                        world,
+                        &party.id,
+                        PlayActorVfxOnPositionPayload {
+                            vfx_path: tower.attack_vfx.clone(),
+                            world_position_x: position.x,
+                            world_position_y: position.y,
+                            world_position_z: position.z,
+                            rotation: rotation.value,
+                        },
+                    );
+
+                    tower.phase = Phase::Attack;
+                    return;
+                }
+
+                Phase::Attack => {
+                    tower.effect_delay -= it.delta_time();
+                    if tower.effect_delay > 0.0 {
+                        return;
+                    }
+
+                    if let Some(on_soak) = tower.on_soak {
+                        entity.try_get::<&Affects>(|a| {
+                            for (e, &count) in &a.player_entities {
+                                if let Some(player) = get_entity_view(e, world) {
+                                    on_soak(&player, count);
+                                }
+                            }
+                        });
+                    }
+
+                    if tower.failed {
+                        tower.phase = Phase::Failure;
+                        return;
+                    }
+                }
+
+                Phase::Failure => {
+                    tower.failure_attack_delay -= it.delta_time();
+                    if tower.failure_attack_delay > 0.0 {
+                        return;
+                    }
+
+                    broadcast_play_actor_vfx_on_position(
+                        world,
+                        &party.id,
+                        PlayActorVfxOnPositionPayload {
+                            vfx_path: tower.failure_attack_vfx.clone(),
+                            world_position_x: position.x,
+                            world_position_y: position.y,
+                            world_position_z: position.z,
+                            rotation: rotation.value,
+                        },
+                    );
+
+                    tower.phase = Phase::FailureAttack;
+                    return;
+                }
+
+                Phase::FailureAttack => {
+                    tower.failure_effect_delay -= it.delta_time();
+                    if tower.failure_effect_delay > 0.0 {
+                        return;
+                    }
+
+                    if let Some(on_failure) = tower.on_failure
+                        && let Some(pc) = find_party_container(world, &party.id)
+                    {
+                        pc.each_child(|c| {
+                            c.try_get::<(&Player, &State)>(|(_, s)| {
+                                if s.is_alive {
+                                    on_failure(&c);
+                                }
+                            });
+                        });
+                    }
+                }
+            }
+
+            info!(
+                mechanic.request_id,
+                mechanic.mechanic_id, party.id, "Completing Mechanic"
+            );
+            entity.remove(Tower::id());
+        });
+}
@@ -0,0 +1,277 @@
+use crate::{
+    game::{
+        components::*,
+        condition::{self, apply_condition},
+        movement::{RESOLVE_DELAY, get_position_at},
+        utils::*,
+    },
+    webserver::message::*,
+};
+use flecs_ecs::prelude::*;
+use std::time::Instant;
+use tracing::info;
+use uuid::Uuid;
+
+// Rows of circles that advance step by step in the mechanic's facing direction, starting from each start position.
+// Each step's omen appears when the previous step goes off. Players are hit based on where they were when
+// the step went off, looked up from their position history, so every client agrees on who was hit.
+//
+// Configured through the extra data as "step_distance,step_interval,step_count,x1,z1,x2,z2,...",
+// where the start positions are at the mechanic's y. Without start positions, the mechanic's position is used.
+// Any missing or invalid value is left at its default, and the step count and start positions are capped.
+
+// Limits on what a client can ask for, since every step of every row is laid out up front
+const MAX_STEP_COUNT: u32 = 32;
+const MAX_START_POSITIONS: usize = 32;
+
+#[derive(Component, Debug)]
+pub struct Exaflare {
+    // Settings
+    step_distance: f32,
+    step_interval: f32,
+    step_count: u32,
+    start_positions: Vec<(f32, f32)>,
+    time_to_first_step: f32,
+    radius: f32,
+    stun_duration: f32,
+    pacify_duration: f32,
+    direction_omen_vfx_path: String,
+    omen_vfx_path: String,
+    attack_vfx_path: String,
+    // Runtime
+    elapsed_time: f32,
+    steps: Vec<ExaflareStep>,
+}
+
+#[derive(Debug)]
+struct ExaflareStep {
+    position: Position,
+    index: u32,
+    // Omen vfx entities
+    omens: Vec<Entity>,
+    omen_sent: bool,
+    hit_time: Option<Instant>,
+}
+
+pub fn create_mechanic(entity: EntityView<'_>) -> EntityView<'_> {
+    let mut exaflare = Exaflare {
+        // Settings
+        step_distance: 8.0,
+        step_interval: 1.5,
+        step_count: 6,
+        start_positions: Vec::new(),
+        time_to_first_step: 4.0,
+        radius: 6.0,
+        stun_duration: 10.0,
+        pacify_duration: 20.0,
+        direction_omen_vfx_path: "vfx/omen/eff/yazirushi1o0c.avfx".to_string(),
+        omen_vfx_path: "vfx/omen/eff/general_1bf.avfx".to_string(),
+        attack_vfx_path: "vfx/monster/gimmick2/eff/f1bz_b0_g02c0i.avfx".to_string(),
+        // Runtime
+        elapsed_time: 0.0,
+        steps: Vec::new(),
+    };
+
+    entity.try_get::<&ExtraMechanicData>(|ed| {
+        let values: Vec<&str> = ed.value.split(',').map(str::trim).collect();
+        if let Some(Ok(d)) = values.first().map(|v| v.parse::<f32>())
+            && d.is_finite()
+            && d > 0.0
+        {
+            exaflare.step_distance = d;
+        }
+        if let Some(Ok(i)) = values.get(1).map(|v| v.parse::<f32>())
+            && i.is_finite()
+            && i > 0.0
+        {
+            exaflare.step_interval = i;
+        }
+        if let Some(Ok(n)) = values.get(2).map(|v| v.parse::<u32>()) {
+            exaflare.step_count = n.min(MAX_STEP_COUNT);
+        }
+        for pair in values
+            .get(3..)
+            .unwrap_or_default()
+            .chunks_exact(2)
+            .take(MAX_START_POSITIONS)
+        {
+            if let (Ok(x), Ok(z)) = (pair[0].parse::<f32>(), pair[1].parse::<f32>())
+                && x.is_finite()
+                && z.is_finite()
+            {
+                exaflare.start_positions.push((x, z));
+            }
+        }
+    });
+
+    entity.set(exaflare)
+}
+
+pub fn create_systems(world: &World) {
+    world
+        .system::<(&Mechanic, &mut Exaflare, &Position, &Rotation, &Party)>()
+        .term_at(4)
+        .up()
+        .each_iter(
+            |it, index, (mechanic, exaflare, position, rotation, party)| {
+                let entity = it.entity(index);
+                let world = &it.world();
+
+                if exaflare.steps.is_empty() {
+                    // Lay out every step of every row
+                    let start_positions = if exaflare.start_positions.is_empty() {
+                        vec![(position.x, position.z)]
+                    } else {
+                        exaflare.start_positions.clone()
+                    };
+                    let (sin, cos) = rotation.value.sin_cos();
+                    for (x, z) in start_positions {
+                        for i in 0..exaflare.step_count {
+                            let distance = i as f32 * exaflare.step_distance;
+                            exaflare.steps.push(ExaflareStep {
+                                position: Position {
+                                    x: x + distance * sin,
+                                    y: position.y,
+                                    z: z + distance * cos,
+                                },
+                                index: i,
+                                omens: Vec::new(),
+                                omen_sent: false,
+                                hit_time: None,
+                            });
+                        }
+                    }
+                    if exaflare.steps.is_empty() {
+                        info!(
+                            mechanic.request_id,
+                            mechanic.mechanic_id, party.id, "Completing Mechanic"
+                        );
+                        entity.remove(Exaflare::id());
+                        return;
+                    }
+                }
+
+                exaflare.elapsed_time += it.delta_time();
+                let now = Instant::now();
+                let extent = entity.try_get::<&VerticalExtent>(|v| *v);
+
+                for step in &mut exaflare.steps {
+                    let step_time =
+                        exaflare.time_to_first_step + step.index as f32 * exaflare.step_interval;
+
+                    // Send omen vfx when the previous step goes off
+                    let omen_time = if step.index == 0 {
+                        0.0
+                    } else {
+                        step_time - exaflare.step_interval
+                    };
+                    if !step.omen_sent && exaflare.elapsed_time >= omen_time {
+                        step.omen_sent = true;
+                        let mut vfx_paths = vec![&exaflare.omen_vfx_path];
+                        if step.index == 0 {
+                            vfx_paths.push(&exaflare.direction_omen_vfx_path);
+                        }
+                        for vfx_path in vfx_paths {
+                            let vfx_id = Uuid::new_v4().as_u128();
+                            step.omens
+                                .push(*world.entity().child_of(entity).set(Vfx { id: vfx_id }));
+                            broadcast_play_static_vfx(
+                                world,
+                                &party.id,
+                                PlayStaticVfxPayload {
+                                    id: vfx_id,
+                                    vfx_path: vfx_path.clone(),
+                                    is_omen: true,
+                                    world_position_x: step.position.x,
+                                    world_position_y: step.position.y,
+                                    world_position_z: step.position.z,
+                                    rotation: rotation.value,
+                                    scale_x: Some(exaflare.radius),
+                                    scale_y: Some(exaflare.radius),
+                                    scale_z: Some(exaflare.radius),
+                                },
+                            );
+                        }
+                    }
+
+                    // Go off
+                    if step.hit_time.is_none() && exaflare.elapsed_time >= step_time {
+                        step.hit_time = Some(now);
+                        for omen in step.omens.drain(..) {
+                            omen.entity_view(world).destruct();
+                        }
+                        broadcast_play_actor_vfx_on_position(
+                            world,
+                            &party.id,
+                            PlayActorVfxOnPositionPayload {
+                                vfx_path: exaflare.attack_vfx_path.clone(),
+                                world_position_x: step.position.x,
+                                world_position_y: step.position.y,
+                                world_position_z: step.position.z,
+                                rotation: rotation.value,
+                            },
+                        );
+                    }
+                }
+
+                // Resolve hits
+                exaflare.steps.retain(|step| {
+                    let Some(hit_time) = step.hit_time else {
+                        return true;
+                    };
+                    if now.duration_since(hit_time) < RESOLVE_DELAY {
+                        return true;
+                    }
+
+                    if let Some(pc) = find_party_container(world, &party.id) {
+                        pc.each_child(|c| {
+                            if c.has(Stale::id()) {
+                                return;
+                            }
+                            c.try_get::<(&Player, &State)>(|(pl, s)| {
+                                if !s.is_alive {
+                                    return;
+                                }
+                                if let Some(p) = get_position_at(&c, hit_time)
+                                    && is_in_circle(
+                                        &step.position,
+                                        exaflare.radius,
+                                        extent.as_ref(),
+                                        &p,
+                                    )
+                                {
+                                    info!(
+                                        mechanic.request_id,
+                                        pl.content_id, step.index, "Exaflare hit player"
+                                    );
+                                    apply_condition(
+                                        &c,
+                                        condition::Condition::Stun as u128,
+                                        condition::Condition::Stun,
+                                        exaflare.stun_duration,
+                                        false,
+                                    );
+                                    apply_condition(
+                                        &c,
+                                        condition::Condition::Pacify as u128,
+                                        condition::Condition::Pacify,
+                                        exaflare.pacify_duration,
+                                        false,
+                                    );
+                                }
+                            });
+                        });
+                    }
+                    false
+                });
+
+                if exaflare.steps.is_empty() {
+                    info!(
+                        mechanic.request_id,
+                        mechanic.mechanic_id, party.id, "Completing Mechanic"
+                    );
+                    entity.remove(Exaflare::id());
+                }
+            },
+        );
+}
@@ -0,0 +1,213 @@
+use crate::game::{
+    components::*,
+    condition::{self, apply_condition},
+    projectile::{Projectile, spawn_projectile},
+    utils::*,
+};
+use flecs_ecs::prelude::*;
+use rand::Rng;
+use tracing::info;
+
+// A ball that drops in at the mechanic's position, then rolls in the mechanic's facing direction, bouncing
+// off the edge of a circular arena. Players it rolls over are knocked back away from it.
+// The ball is a projectile, so it's simulated and hit tested on the server, and clients only show it.
+//
+// Configured through the extra data as "arena_center_x,arena_center_z,arena_radius,max_bounces".
+// Any missing or invalid value is left at its default, and the arena is centered on the mechanic's position by default.
+
+const MODEL_ID: u32 = 1443;
+const HITBOX_RADIUS: f32 = 4.0;
+const HIT_COOLDOWN: f32 = 0.25;
+const KNOCKBACK_DURATION: f32 = 1.0;
+
+#[derive(Component, Debug)]
+pub struct RollingBall {
+    // Settings
+    time_until_rolling: f32,
+    acceleration: f32,
+    max_speed: f32,
+    reflect_angle_variance_degrees: f32,
+    arena_center_x: f32,
+    arena_center_z: f32,
+    arena_radius: f32,
+    max_bounces: u32,
+    // Runtime
+    ball: Option<Entity>,
+    direction: (f32, f32),
+    speed: f32,
+    bounces: u32,
+}
+
+pub fn create_mechanic(entity: EntityView<'_>) -> EntityView<'_> {
+    let mut rolling_ball = RollingBall {
+        // Settings
+        time_until_rolling: 2.25,
+        acceleration: 25.0,
+        max_speed: 8.75,
+        reflect_angle_variance_degrees: 25.0,
+        arena_center_x: 100.0,
+        arena_center_z: 100.0,
+        arena_radius: 20.0,
+        max_bounces: 3,
+        // Runtime
+        ball: None,
+        direction: (0.0, 1.0),
+        speed: 0.0,
+        bounces: 0,
+    };
+
+    entity.try_get::<&Position>(|p| {
+        rolling_ball.arena_center_x = p.x;
+        rolling_ball.arena_center_z = p.z;
+    });
+    entity.try_get::<&ExtraMechanicData>(|ed| {
+        let values: Vec<Option<f32>> = ed
+            .value
+            .split(',')
+            .map(|v| v.trim().parse::<f32>().ok().filter(|v| v.is_finite()))
+            .collect();
+        if let Some(Some(x)) = values.first() {
+            rolling_ball.arena_center_x = *x;
+        }
+        if let Some(Some(z)) = values.get(1) {
+            rolling_ball.arena_center_z = *z;
+        }
+        if let Some(Some(r)) = values.get(2)
+            && *r > 0.0
+        {
+            rolling_ball.arena_radius = *r;
+        }
+        if let Some(Some(n)) = values.get(3)
+            && *n >= 0.0
+        {
+            rolling_ball.max_bounces = *n as u32;
+        }
+    });
+
+    entity.set(rolling_ball)
+}
+
+fn on_hit(_: &EntityView<'_>, ball_position: &Position, player: &EntityView<'_>) {
+    let Some(player_position) = player.try_get::<&Position>(|p| *p) else {
+        return;
+    };
+
+    // Knock the player away from the ball, or somewhere random if they're right on top of it
+    let (mut x, mut z) = (
+        player_position.x - ball_position.x,
+        player_position.z - ball_position.z,
+    );
+    let length = (x.powi(2) + z.powi(2)).sqrt();
+    if length > 0.0 {
+        x /= length;
+        z /= length;
+    } else {
+        let angle = rand::rng().random_range(0.0..std::f32::consts::TAU);
+        (x, z) = angle.sin_cos();
+    }
+
+    player.try_get::<&Player>(|pl| {
+        info!(pl.content_id, "Rolling ball hit player");
+    });
+    apply_condition(
+        player,
+        condition::Condition::Knockback as u128,
+        condition::Condition::Knockback,
+        KNOCKBACK_DURATION,
+        true,
+    )
+    .entity_view(player.world())
+    .set(conditions::Knockback {
+        knockback_direction_x: x,
+        knockback_direction_z: z,
+    });
+}
+
+pub fn create_systems(world: &World) {
+    world
+        .system::<(&Mechanic, &mut RollingBall, &Position, &Rotation, &Party)>()
+        .term_at(4)
+        .up()
+        .each_iter(
+            |it, index, (mechanic, rolling_ball, position, rotation, party)| {
+                let entity = it.entity(index);
+                let world = &it.world();
+
+                let Some(ball) = rolling_ball.ball else {
+                    // Drop in the ball
+                    let mut projectile =
+                        Projectile::new(MODEL_ID.to_string(), HITBOX_RADIUS, HIT_COOLDOWN, on_hit);
+                    projectile.time_until_active = rolling_ball.time_until_rolling;
+                    rolling_ball.ball = Some(spawn_projectile(
+                        &entity,
+                        &party.id,
+                        projectile,
+                        *position,
+                        Velocity::default(),
+                    ));
+                    rolling_ball.direction = rotation.value.sin_cos();
+                    return;
+                };
+
+                let Some(ball) = get_entity_view(&ball, world) else {
+                    info!(
+                        mechanic.request_id,
+                        mechanic.mechanic_id, party.id, "Completing Mechanic"
+                    );
+                    entity.remove(RollingBall::id());
+                    return;
+                };
+
+                if rolling_ball.time_until_rolling > 0.0 {
+                    rolling_ball.time_until_rolling =
+                        f32::max(rolling_ball.time_until_rolling - it.delta_time(), 0.0);
+                    return;
+                }
+
+                rolling_ball.speed = f32::min(
+                    rolling_ball.speed + rolling_ball.acceleration * it.delta_time(),
+                    rolling_ball.max_speed,
+                );
+
+                // Bounce off the arena edge
+                let Some(ball_position) = ball.try_get::<&Position>(|p| *p) else {
+                    return;
+                };
+                let (to_center_x, to_center_z) = (
+                    rolling_ball.arena_center_x - ball_position.x,
+                    rolling_ball.arena_center_z - ball_position.z,
+                );
+                let distance = (to_center_x.powi(2) + to_center_z.powi(2)).sqrt();
+                let (dx, dz) = rolling_ball.direction;
+                if distance > rolling_ball.arena_radius && dx * to_center_x + dz * to_center_z < 0.0
+                {
+                    if rolling_ball.bounces >= rolling_ball.max_bounces {
+                        info!(
+                            mechanic.request_id,
+                            mechanic.mechanic_id, party.id, "Completing Mechanic"
+                        );
+                        ball.destruct();
+                        entity.remove(RollingBall::id());
+                        return;
+                    }
+                    rolling_ball.bounces += 1;
+
+                    let (nx, nz) = (to_center_x / distance, to_center_z / distance);
+                    let dot = dx * nx + dz * nz;
+                    let (rx, rz) = (dx - 2.0 * dot * nx, dz - 2.0 * dot * nz);
+                    let half_variance =
+                        rolling_ball.reflect_angle_variance_degrees.to_radians() / 2.0;
+                    let variance = rand::rng().random_range(-half_variance..=half_variance);
+                    let (sin, cos) = variance.sin_cos();
+                    rolling_ball.direction = (rx * cos + rz * sin, rz * cos - rx * sin);
+                    rolling_ball.speed = 0.0;
+                }
+
+                ball.set(Velocity {
+                    x: rolling_ball.direction.0 * rolling_ball.speed,
+                    y: 0.0,
+                    z: rolling_ball.direction.1 * rolling_ball.speed,
+                });
+            },
+        );
+}
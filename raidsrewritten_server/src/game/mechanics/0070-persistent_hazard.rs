@@ -0,0 +1,207 @@
+use crate::{
+    game::{
+        components::*,
+        condition::{self, apply_condition_stack},
+        utils::*,
+    },
+    webserver::message::PlayStaticVfxPayload,
+};
+use flecs_ecs::prelude::*;
+use strum::IntoEnumIterator;
+use tracing::info;
+use uuid::Uuid;
+
+// A puddle on the ground that stays for its lifetime, optionally growing or shrinking from start_radius to end_radius.
+// Every tick_interval, each living player standing in it gets another stack of the condition.
+// It's removed when its lifetime runs out, or when the mechanic is cleared, which stops its vfx.
+//
+// Configured through the extra data as
+// "start_radius,end_radius,expand_speed,lifetime,tick_interval,condition,condition_duration,max_stacks",
+// where condition is a Condition number. Any missing or invalid value is left at its default.
+
+// The puddle vfx's radius at a scale of 1
+const VFX_RADIUS: f32 = 5.0;
+// While resizing, the vfx is replaced with a rescaled one whenever the radius has changed by this much
+const VFX_RESCALE_STEP: f32 = 0.5;
+
+#[derive(Component, Debug)]
+pub struct PersistentHazard {
+    // Settings
+    end_radius: f32,
+    expand_speed: f32,
+    tick_interval: f32,
+    condition: condition::Condition,
+    condition_duration: f32,
+    max_stacks: u8,
+    vfx_path: String,
+    // Runtime
+    radius: f32,
+    vfx: Option<Entity>,
+    vfx_radius: f32,
+    lifetime: f32,
+    time_to_next_tick: f32,
+}
+
+pub fn create_mechanic(entity: EntityView<'_>) -> EntityView<'_> {
+    let mut hazard = PersistentHazard {
+        // Settings
+        end_radius: 5.0,
+        expand_speed: 0.0,
+        tick_interval: 1.0,
+        condition: condition::Condition::Heavy,
+        condition_duration: 3.0,
+        max_stacks: 8,
+        vfx_path: "bgcommon/world/common/vfx_for_btl/b0195/eff/b0195_yuka_c.avfx".to_string(),
+        // Runtime
+        radius: 5.0,
+        vfx: None,
+        vfx_radius: 0.0,
+        lifetime: 20.0,
+        time_to_next_tick: 0.0,
+    };
+
+    entity.try_get::<&ExtraMechanicData>(|ed| {
+        let values: Vec<Option<f32>> = ed
+            .value
+            .split(',')
+            .map(|v| v.trim().parse::<f32>().ok().filter(|v| v.is_finite()))
+            .collect();
+        if let Some(Some(r)) = values.first()
+            && *r >= 0.0
+        {
+            hazard.radius = *r;
+            hazard.end_radius = *r;
+        }
+        if let Some(Some(r)) = values.get(1)
+            && *r >= 0.0
+        {
+            hazard.end_radius = *r;
+        }
+        if let Some(Some(s)) = values.get(2)
+            && *s >= 0.0
+        {
+            hazard.expand_speed = *s;
+        }
+        if let Some(Some(l)) = values.get(3) {
+            hazard.lifetime = *l;
+        }
+        if let Some(Some(i)) = values.get(4)
+            && *i > 0.0
+        {
+            hazard.tick_interval = *i;
+        }
+        if let Some(Some(c)) = values.get(5)
+            && let Some(c) = condition::Condition::iter().find(|v| *v as u32 as f32 == *c)
+            && c != condition::Condition::None
+        {
+            hazard.condition = c;
+        }
+        if let Some(Some(d)) = values.get(6)
+            && *d > 0.0
+        {
+            hazard.condition_duration = *d;
+        }
+        if let Some(Some(n)) = values.get(7)
+            && *n >= 1.0
+        {
+            hazard.max_stacks = n.min(u8::MAX as f32) as u8;
+        }
+    });
+
+    entity.set(hazard)
+}
+
+pub fn create_systems(world: &World) {
+    world
+        .system::<(
+            &Mechanic,
+            &mut PersistentHazard,
+            &Position,
+            &Rotation,
+            &Party,
+        )>()
+        .term_at(4)
+        .up()
+        .each_iter(|it, index, (mechanic, hazard, position, rotation, party)| {
+            let entity = it.entity(index);
+            let world = &it.world();
+
+            hazard.lifetime -= it.delta_time();
+            if hazard.lifetime <= 0.0 {
+                if let Some(vfx) = hazard.vfx.take() {
+                    vfx.entity_view(world).destruct();
+                }
+                info!(
+                    mechanic.request_id,
+                    mechanic.mechanic_id, party.id, "Completing Mechanic"
+                );
+                entity.remove(PersistentHazard::id());
+                return;
+            }
+
+            let step = hazard.expand_speed * it.delta_time();
+            if hazard.radius < hazard.end_radius {
+                hazard.radius = f32::min(hazard.radius + step, hazard.end_radius);
+            } else if hazard.radius > hazard.end_radius {
+                hazard.radius = f32::max(hazard.radius - step, hazard.end_radius);
+            }
+
+            // Send all players the puddle vfx, replacing it as it's resized
+            if hazard.vfx.is_none()
+                || (hazard.radius - hazard.vfx_radius).abs() >= VFX_RESCALE_STEP
+                || (hazard.radius == hazard.end_radius && hazard.vfx_radius != hazard.end_radius)
+            {
+                if let Some(old_vfx) = hazard.vfx.take() {
+                    old_vfx.entity_view(world).destruct();
+                }
+                let vfx_id = Uuid::new_v4().as_u128();
+                hazard.vfx = Some(*world.entity().child_of(entity).set(Vfx { id: vfx_id }));
+                hazard.vfx_radius = hazard.radius;
+
+                let scale = hazard.radius / VFX_RADIUS;
+                broadcast_play_static_vfx(
+                    world,
+                    &party.id,
+                    PlayStaticVfxPayload {
+                        id: vfx_id,
+                        vfx_path: hazard.vfx_path.clone(),
+                        is_omen: false,
+                        world_position_x: position.x,
+                        world_position_y: position.y,
+                        world_position_z: position.z,
+                        rotation: rotation.value,
+                        scale_x: Some(scale),
+                        scale_y: Some(scale),
+                        scale_z: Some(scale),
+                    },
+                );
+            }
+
+            hazard.time_to_next_tick -= it.delta_time();
+            if hazard.time_to_next_tick > 0.0 {
+                return;
+            }
+            hazard.time_to_next_tick += hazard.tick_interval;
+
+            // Damage tick
+            let extent = entity.try_get::<&VerticalExtent>(|v| *v);
+            if let Some(pc) = find_party_container(world, &party.id) {
+                pc.each_child(|c| {
+                    if c.has(Stale::id()) {
+                        return;
+                    }
+                    c.try_get::<(&Player, &Position, &State)>(|(_, p, s)| {
+                        if s.is_alive && is_in_circle(position, hazard.radius, extent.as_ref(), p) {
+                            apply_condition_stack(
+                                &c,
+                                hazard.condition as u128,
+                                hazard.condition,
+                                hazard.condition_duration,
+                                hazard.max_stacks,
+                            );
+                        }
+                    });
+                });
+            }
+        });
+}
@@ -0,0 +1,315 @@
+use crate::{
+    game::{
+        components::*,
+        condition::{self, apply_condition},
+        role,
+        utils::*,
+    },
+    webserver::message::{PlayActorVfxOnTargetPayload, PlayTetherVfxPayload},
+};
+use distances::vectors::euclidean_sq;
+use flecs_ecs::prelude::*;
+use rand::seq::SliceRandom;
+use std::collections::{HashMap, HashSet};
+use tracing::info;
+use uuid::Uuid;
+
+// Tethers between pairs of players, which snapshot the distance between each pair.
+// Both players of a pair are punished if they're closer than min_distance or further than max_distance.
+// Pairs with a dead, stale or departed player are skipped, since there's nothing fair to check them against.
+//
+// Configured through the extra data as "min_distance,max_distance,pairing,...", where pairing is one of
+// - "random" (the default), optionally followed by a pair count, pairing up random players
+// - "role", optionally followed by a pair count, pairing a tank or healer with a dps
+// - "ids", followed by content ids, pairing them up in order. Pairs of the same player, or with a player
+//   that's already paired, are skipped
+// Without a max_distance, the tether has to be stretched past min_distance, or kept within 10 without a min_distance.
+// Any other missing or invalid value is left at its default.
+
+const DEFAULT_MAX_DISTANCE: f32 = 10.0;
+
+#[derive(Debug)]
+enum Pairing {
+    Random(usize),
+    Role(usize),
+    ContentIds(Vec<u64>),
+}
+
+#[derive(Debug)]
+struct TetherPair {
+    source: Entity,
+    target: Entity,
+    // Tether vfx entity
+    vfx: Entity,
+}
+
+#[derive(Component, Debug)]
+pub struct Tether {
+    // Settings
+    min_distance: f32,
+    max_distance: f32,
+    pairing: Pairing,
+    time_to_snapshot: f32,
+    stun_duration: f32,
+    close_tether_vfx_path: String,
+    far_tether_vfx_path: String,
+    failure_vfx_paths: Vec<String>,
+    success_vfx_paths: Vec<String>,
+    // Runtime
+    pairs: Vec<TetherPair>,
+}
+
+pub fn create_mechanic(entity: EntityView<'_>) -> EntityView<'_> {
+    let mut tether = Tether {
+        // Settings
+        min_distance: 0.0,
+        max_distance: DEFAULT_MAX_DISTANCE,
+        pairing: Pairing::Random(usize::MAX),
+        time_to_snapshot: 8.0,
+        stun_duration: 15.0,
+        close_tether_vfx_path: "vfx/channeling/eff/chn_alpha0h.avfx".to_string(),
+        far_tether_vfx_path: "vfx/channeling/eff/chn_beta0h.avfx".to_string(),
+        failure_vfx_paths: vec![
+            "vfx/lockon/eff/m0489trg_b0c.avfx".to_string(),
+            "vfx/monster/m0005/eff/m0005sp_15t0t.avfx".to_string(),
+        ],
+        success_vfx_paths: vec!["vfx/lockon/eff/m0489trg_a0c.avfx".to_string()],
+        // Runtime
+        pairs: Vec::new(),
+    };
+
+    entity.try_get::<&ExtraMechanicData>(|ed| {
+        let values: Vec<&str> = ed.value.split(',').map(str::trim).collect();
+        if let Some(Ok(d)) = values.first().map(|v| v.parse::<f32>())
+            && d.is_finite()
+            && d >= 0.0
+        {
+            tether.min_distance = d;
+        }
+        if let Some(Ok(d)) = values.get(1).map(|v| v.parse::<f32>())
+            && d.is_finite()
+            && d >= 0.0
+        {
+            tether.max_distance = d;
+        } else if tether.min_distance > 0.0 {
+            tether.max_distance = f32::INFINITY;
+        }
+        let count = values.get(3).and_then(|v| v.parse().ok());
+        match values.get(2).copied() {
+            Some("role") => tether.pairing = Pairing::Role(count.unwrap_or(usize::MAX)),
+            Some("ids") => {
+                tether.pairing =
+                    Pairing::ContentIds(values[3..].iter().filter_map(|v| v.parse().ok()).collect())
+            }
+            _ => tether.pairing = Pairing::Random(count.unwrap_or(usize::MAX)),
+        }
+    });
+
+    entity.set(tether)
+}
+
+fn is_support(player: &EntityView<'_>) -> bool {
+    player
+        .try_get::<&Role>(|r| r.role == role::Role::Tank || r.role == role::Role::Healer)
+        .unwrap_or(false)
+}
+
+// Only living players who are still connected are paired, since pairs with anyone else are skipped at snapshot
+fn pair_players(pairing: &Pairing, party_container: &EntityView<'_>) -> Vec<(Entity, Entity)> {
+    let mut players: Vec<Entity> = Vec::new();
+    party_container.each_child(|c| {
+        if c.has(Stale::id()) {
+            return;
+        }
+        c.try_get::<(&Player, &State)>(|(_, s)| {
+            if s.is_alive {
+                players.push(*c);
+            }
+        });
+    });
+
+    let rng = &mut rand::rng();
+    match pairing {
+        Pairing::Random(count) => {
+            players.shuffle(rng);
+            players
+                .chunks_exact(2)
+                .take(*count)
+                .map(|p| (p[0], p[1]))
+                .collect()
+        }
+        Pairing::Role(count) => {
+            let world = party_container.world();
+            let (mut supports, mut dps): (Vec<Entity>, Vec<Entity>) = players
+                .into_iter()
+                .partition(|p| is_support(&p.entity_view(world)));
+            supports.shuffle(rng);
+            dps.shuffle(rng);
+            supports.into_iter().zip(dps).take(*count).collect()
+        }
+        Pairing::ContentIds(content_ids) => {
+            let world = party_container.world();
+            let by_content_id: HashMap<u64, Entity> = players
+                .into_iter()
+                .filter_map(|p| {
+                    p.entity_view(world)
+                        .try_get::<&Player>(|pl| (pl.content_id, p))
+                })
+                .collect();
+            let mut paired: HashSet<u64> = HashSet::new();
+            content_ids
+                .chunks_exact(2)
+                .filter_map(|ids| {
+                    if ids[0] == ids[1] || paired.contains(&ids[0]) || paired.contains(&ids[1]) {
+                        return None;
+                    }
+                    let pair = (*by_content_id.get(&ids[0])?, *by_content_id.get(&ids[1])?);
+                    paired.extend([ids[0], ids[1]]);
+                    Some(pair)
+                })
+                .collect()
+        }
+    }
+}
+
+pub fn create_systems(world: &World) {
+    world
+        .system::<(&Mechanic, &mut Tether, &Party)>()
+        .term_at(2)
+        .up()
+        .each_iter(|it, index, (mechanic, tether, party)| {
+            let entity = it.entity(index);
+            let world = &it.world();
+
+            if !entity.has(Targets::id()) {
+                // Pair up players
+                let pairs = find_party_container(world, &party.id)
+                    .map(|pc| pair_players(&tether.pairing, &pc))
+                    .unwrap_or_default();
+
+                // Send tether vfx
+                let vfx_path = if tether.min_distance > 0.0 {
+                    &tether.far_tether_vfx_path
+                } else {
+                    &tether.close_tether_vfx_path
+                };
+                for (source, target) in &pairs {
+                    let content_id = |e: &Entity| {
+                        e.entity_view(world)
+                            .try_get::<&Player>(|pl| pl.content_id)
+                            .unwrap_or_default()
+                    };
+                    let vfx_id = Uuid::new_v4().as_u128();
+                    tether.pairs.push(TetherPair {
+                        source: *source,
+                        target: *target,
+                        vfx: *world.entity().child_of(entity).set(Vfx { id: vfx_id }),
+                    });
+                    broadcast_play_tether_vfx(
+                        world,
+                        &party.id,
+                        PlayTetherVfxPayload {
+                            id: vfx_id,
+                            vfx_path: vfx_path.clone(),
+                            content_id_source: content_id(source),
+                            content_id_target: content_id(target),
+                        },
+                    );
+                }
+
+                entity.set(Targets {
+                    player_entities: pairs.iter().flat_map(|(s, t)| [*s, *t]).collect(),
+                });
+                return;
+            }
+
+            tether.time_to_snapshot -= it.delta_time();
+
+            if tether.time_to_snapshot > 0.0 {
+                return;
+            }
+
+            // Snapshot
+            let targets = entity
+                .try_get::<&Targets>(|t| t.player_entities.clone())
+                .unwrap_or_default();
+            let mut failed: Vec<u64> = Vec::new();
+            let mut succeeded: Vec<u64> = Vec::new();
+            for pair in &tether.pairs {
+                pair.vfx.entity_view(world).destruct();
+
+                // Players who left were dropped from the targets
+                if !targets.contains(&pair.source) || !targets.contains(&pair.target) {
+                    continue;
+                }
+                let (Some(source), Some(target)) = (
+                    get_entity_view(&pair.source, world),
+                    get_entity_view(&pair.target, world),
+                ) else {
+                    continue;
+                };
+                if source.has(Stale::id()) || target.has(Stale::id()) {
+                    continue;
+                }
+                let snapshot = |e: &EntityView<'_>| {
+                    e.try_get::<(&Player, &Position, &State)>(|(pl, p, s)| {
+                        s.is_alive.then_some((pl.content_id, *p))
+                    })
+                    .flatten()
+                };
+                let (Some((source_id, p1)), Some((target_id, p2))) =
+                    (snapshot(&source), snapshot(&target))
+                else {
+                    continue;
+                };
+
+                let distance_sq: f32 = euclidean_sq(&[p1.x, p1.z], &[p2.x, p2.z]);
+                let distance = distance_sq.sqrt();
+                if distance < tether.min_distance || distance > tether.max_distance {
+                    info!(
+                        mechanic.request_id,
+                        source_id, target_id, distance, "Tether failed"
+                    );
+                    for player in [&source, &target] {
+                        apply_condition(
+                            player,
+                            condition::Condition::Stun as u128,
+                            condition::Condition::Stun,
+                            tether.stun_duration,
+                            false,
+                        );
+                    }
+                    failed.extend([source_id, target_id]);
+                } else {
+                    succeeded.extend([source_id, target_id]);
+                }
+            }
+
+            // Send resolution vfx
+            for (vfx_paths, targets) in [
+                (&tether.failure_vfx_paths, failed),
+                (&tether.success_vfx_paths, succeeded),
+            ] {
+                if targets.is_empty() {
+                    continue;
+                }
+                for vfx_path in vfx_paths {
+                    broadcast_play_actor_vfx_on_target(
+                        world,
+                        &party.id,
+                        PlayActorVfxOnTargetPayload {
+                            vfx_path: vfx_path.clone(),
+                            content_id_targets: targets.clone(),
+                            ..Default::default()
+                        },
+                    );
+                }
+            }
+
+            info!(
+                mechanic.request_id,
+                mechanic.mechanic_id, party.id, "Completing Mechanic"
+            );
+            entity.remove(Tether::id());
+        });
+}
@@ -0,0 +1,247 @@
+use crate::{
+    game::{
+        components::*,
+        condition::{Condition, apply_condition},
+        utils::*,
+    },
+    webserver::message::PlayActorVfxOnTargetPayload,
+};
+use distances::vectors::euclidean_sq;
+use flecs_ecs::prelude::*;
+use rand::seq::IndexedRandom;
+use tracing::info;
+
+// Lightning that strikes one player, then jumps to the nearest living player within bounce_range of the last one hit,
+// up to max_bounces times. No player is hit twice. Each hop is punished harder than the one before it,
+// so the party has to spread out to stop the chain early.
+// If the target leaves before the first hit, the lightning moves to another player.
+//
+// Configured through the extra data as "max_bounces,bounce_range,target_content_id".
+// Without a target, or if the target is dead or disconnected, a random living player is picked.
+// Any missing or invalid value is left at its default.
+
+// Conditions applied by each hop. Hops past the end repeat the last entry, lasting longer each time.
+const ESCALATION: [(Condition, f32); 3] = [
+    (Condition::Heavy, 10.0),
+    (Condition::Bind, 5.0),
+    (Condition::Stun, 5.0),
+];
+
+#[derive(Component, Debug)]
+pub struct ChainLightning {
+    // Settings
+    max_bounces: u32,
+    bounce_range: f32,
+    target_content_id: Option<u64>,
+    time_to_first_hit: f32,
+    hop_interval: f32,
+    omen_vfx_path: String,
+    attack_vfx_path: String,
+    // Runtime
+    hit: Vec<Entity>,
+    time_to_next_hop: f32,
+}
+
+pub fn create_mechanic(entity: EntityView<'_>) -> EntityView<'_> {
+    let mut chain_lightning = ChainLightning {
+        // Settings
+        max_bounces: 3,
+        bounce_range: 5.0,
+        target_content_id: None,
+        time_to_first_hit: 5.0,
+        hop_interval: 0.3,
+        omen_vfx_path: "vfx/lockon/eff/target_ae_s5f.avfx".to_string(),
+        attack_vfx_path: "vfx/monster/gimmick/eff/crystal2_chein_kakusan_c0c.avfx".to_string(),
+        // Runtime
+        hit: Vec::new(),
+        time_to_next_hop: 0.0,
+    };
+
+    entity.try_get::<&ExtraMechanicData>(|ed| {
+        let values: Vec<&str> = ed.value.split(',').map(str::trim).collect();
+        if let Some(Ok(n)) = values.first().map(|v| v.parse()) {
+            chain_lightning.max_bounces = n;
+        }
+        if let Some(Ok(r)) = values.get(1).map(|v| v.parse::<f32>())
+            && r.is_finite()
+            && r > 0.0
+        {
+            chain_lightning.bounce_range = r;
+        }
+        if let Some(Ok(id)) = values.get(2).map(|v| v.parse()) {
+            chain_lightning.target_content_id = Some(id);
+        }
+    });
+
+    entity
+        .set(OnTargetLeave {
+            policy: TargetLeavePolicy::Transfer,
+        })
+        .set(chain_lightning)
+}
+
+fn is_hittable(player: &EntityView<'_>) -> bool {
+    !player.has(Stale::id())
+        && player
+            .try_get::<(&Player, &State)>(|(_, s)| s.is_alive)
+            .unwrap_or(false)
+}
+
+pub fn create_systems(world: &World) {
+    world
+        .system::<(&Mechanic, &mut ChainLightning, &Party)>()
+        .term_at(2)
+        .up()
+        .each_iter(|it, index, (mechanic, chain_lightning, party)| {
+            let entity = it.entity(index);
+            let world = &it.world();
+
+            if !entity.has(Targets::id()) {
+                // Assign the target
+                let mut target_players: Vec<Entity> = Vec::new();
+                let mut targets: Vec<u64> = Vec::new();
+
+                if let Some(pc) = find_party_container(world, &party.id) {
+                    let mut players: Vec<Entity> = Vec::new();
+                    pc.each_child(|c| {
+                        if !is_hittable(&c) {
+                            return;
+                        }
+                        c.try_get::<&Player>(|p| {
+                            if chain_lightning.target_content_id == Some(p.content_id) {
+                                target_players.push(*c);
+                            } else {
+                                players.push(*c);
+                            }
+                        });
+                    });
+
+                    if target_players.is_empty()
+                        && let Some(target) = players.choose(&mut rand::rng())
+                    {
+                        target_players.push(*target);
+                    }
+                    if let Some(target) = target_players.first() {
+                        target.entity_view(world).try_get::<&Player>(|p| {
+                            targets.push(p.content_id);
+                        });
+                    }
+                }
+
+                entity.set(Targets {
+                    player_entities: target_players,
+                });
+
+                // Send omen vfx
+                broadcast_play_actor_vfx_on_target(
+                    world,
+                    &party.id,
+                    PlayActorVfxOnTargetPayload {
+                        vfx_path: chain_lightning.omen_vfx_path.clone(),
+                        content_id_targets: targets,
+                        ..Default::default()
+                    },
+                );
+                return;
+            }
+
+            if entity.has(Retargeted::id()) {
+                entity.remove(Retargeted::id());
+                if chain_lightning.hit.is_empty() {
+                    // Move the omen vfx to the new target
+                    broadcast_play_actor_vfx_on_target(
+                        world,
+                        &party.id,
+                        PlayActorVfxOnTargetPayload {
+                            vfx_path: chain_lightning.omen_vfx_path.clone(),
+                            content_id_targets: get_target_ids(&entity),
+                            ..Default::default()
+                        },
+                    );
+                }
+            }
+
+            chain_lightning.time_to_first_hit -= it.delta_time();
+
+            if chain_lightning.time_to_first_hit > 0.0 {
+                return;
+            }
+
+            chain_lightning.time_to_next_hop -= it.delta_time();
+
+            if chain_lightning.time_to_next_hop > 0.0 {
+                return;
+            }
+            chain_lightning.time_to_next_hop = chain_lightning.hop_interval;
+
+            // Find the next player to hit
+            let next = match chain_lightning.hit.last() {
+                None => entity
+                    .try_get::<&Targets>(|t| t.player_entities.first().copied())
+                    .flatten()
+                    .and_then(|e| get_entity_view(&e, world))
+                    .filter(is_hittable)
+                    .map(|ev| *ev),
+                Some(_) if chain_lightning.hit.len() > chain_lightning.max_bounces as usize => None,
+                Some(last) => {
+                    let from =
+                        get_entity_view(last, world).and_then(|ev| ev.try_get::<&Position>(|p| *p));
+                    let mut nearest: Option<(Entity, f32)> = None;
+                    if let Some(from) = from
+                        && let Some(pc) = find_party_container(world, &party.id)
+                    {
+                        pc.each_child(|c| {
+                            if chain_lightning.hit.contains(&c) || !is_hittable(&c) {
+                                return;
+                            }
+                            c.try_get::<&Position>(|p| {
+                                let distance_sq: f32 = euclidean_sq(&[from.x, from.z], &[p.x, p.z]);
+                                if distance_sq <= chain_lightning.bounce_range.powi(2)
+                                    && nearest.is_none_or(|(_, d)| distance_sq < d)
+                                {
+                                    nearest = Some((*c, distance_sq));
+                                }
+                            });
+                        });
+                    }
+                    nearest.map(|(e, _)| e)
+                }
+            };
+
+            let Some(next) = next else {
+                info!(
+                    mechanic.request_id,
+                    mechanic.mechanic_id, party.id, "Completing Mechanic"
+                );
+                entity.remove(ChainLightning::id());
+                return;
+            };
+
+            // Hit
+            let hop = chain_lightning.hit.len();
+            chain_lightning.hit.push(next);
+            let next = next.entity_view(world);
+            let mut content_id_targets: Vec<u64> = Vec::new();
+            next.try_get::<&Player>(|pl| {
+                info!(
+                    mechanic.request_id,
+                    pl.content_id, hop, "Chain lightning hit player"
+                );
+                content_id_targets.push(pl.content_id);
+            });
+            broadcast_play_actor_vfx_on_target(
+                world,
+                &party.id,
+                PlayActorVfxOnTargetPayload {
+                    vfx_path: chain_lightning.attack_vfx_path.clone(),
+                    content_id_targets,
+                    ..Default::default()
+                },
+            );
+
+            let last = ESCALATION.len() - 1;
+            let (condition, duration) = ESCALATION[hop.min(last)];
+            let duration = duration * (hop.saturating_sub(last) + 1) as f32;
+            apply_condition(&next, condition as u128, condition, duration, false);
+        });
+}
@@ -0,0 +1,237 @@
+use crate::{
+    game::{
+        components::*,
+        condition::{self, apply_condition},
+        utils::*,
+    },
+    webserver::message::*,
+};
+use flecs_ecs::prelude::*;
+use strum::IntoEnumIterator;
+use tracing::info;
+use uuid::Uuid;
+
+// A donut between inner_radius and outer_radius around the mechanic's position.
+// With more than one ring, the donut is split into rings which go off one after another, from the outside in.
+// Each ring's omen appears when the previous ring goes off. Players in a ring when it goes off get the condition.
+//
+// Configured through the extra data as "inner_radius,outer_radius,ring_count,ring_interval,condition,condition_duration",
+// where condition is a Condition number. Any missing or invalid value is left at its default, and the ring count is capped.
+
+// Limit on what a client can ask for, since the rings are laid out up front
+const MAX_RING_COUNT: u32 = 8;
+
+// Donut omens and the ratio of their inner radius to their outer radius, which is their scale.
+// Omens can only be drawn at these ratios, so each ring's inner radius is snapped to the omen closest to an
+// equal split of what's left, and it's hit tested against those same radii. Rings that would end up inside
+// inner_radius are dropped, so a donut can have fewer rings than asked for.
+const OMENS: [(&str, f32); 2] = [
+    ("vfx/omen/eff/z5r2_b1_dnt_o0g.avfx", 0.3),
+    ("vfx/omen/eff/gl_sircle_4005bf.avfx", 0.125),
+];
+
+#[derive(Component, Debug)]
+pub struct Donut {
+    // Settings
+    inner_radius: f32,
+    outer_radius: f32,
+    ring_count: u32,
+    ring_interval: f32,
+    time_to_first_ring: f32,
+    condition: condition::Condition,
+    condition_duration: f32,
+    attack_vfx_path: String,
+    // Runtime
+    // Inner radius, outer radius and omen vfx path of each ring, from the outside in
+    rings: Vec<(f32, f32, &'static str)>,
+    elapsed_time: f32,
+    next_ring: u32,
+    // Omen vfx entity of the next ring
+    omen: Option<Entity>,
+}
+
+pub fn create_mechanic(entity: EntityView<'_>) -> EntityView<'_> {
+    let mut donut = Donut {
+        // Settings
+        inner_radius: 12.0,
+        outer_radius: 40.0,
+        ring_count: 1,
+        ring_interval: 2.0,
+        time_to_first_ring: 5.0,
+        condition: condition::Condition::Stun,
+        condition_duration: 10.0,
+        attack_vfx_path: "vfx/monster/gimmick4/eff/z5r2_b1_g01c0g.avfx".to_string(),
+        // Runtime
+        rings: Vec::new(),
+        elapsed_time: 0.0,
+        next_ring: 0,
+        omen: None,
+    };
+
+    entity.try_get::<&ExtraMechanicData>(|ed| {
+        let values: Vec<Option<f32>> = ed
+            .value
+            .split(',')
+            .map(|v| v.trim().parse::<f32>().ok().filter(|v| v.is_finite()))
+            .collect();
+        if let Some(Some(r)) = values.first()
+            && *r >= 0.0
+        {
+            donut.inner_radius = *r;
+        }
+        if let Some(Some(r)) = values.get(1)
+            && *r > 0.0
+        {
+            donut.outer_radius = *r;
+        }
+        if let Some(Some(n)) = values.get(2)
+            && *n >= 1.0
+        {
+            donut.ring_count = u32::min(*n as u32, MAX_RING_COUNT);
+        }
+        if let Some(Some(i)) = values.get(3)
+            && *i >= 0.0
+        {
+            donut.ring_interval = *i;
+        }
+        if let Some(Some(c)) = values.get(4)
+            && let Some(c) = condition::Condition::iter().find(|v| *v as u32 as f32 == *c)
+            && c != condition::Condition::None
+        {
+            donut.condition = c;
+        }
+        if let Some(Some(d)) = values.get(5)
+            && *d > 0.0
+        {
+            donut.condition_duration = *d;
+        }
+    });
+    donut.inner_radius = f32::min(donut.inner_radius, donut.outer_radius);
+    donut.rings = donut.lay_out_rings();
+
+    entity.set(donut)
+}
+
+impl Donut {
+    fn lay_out_rings(&self) -> Vec<(f32, f32, &'static str)> {
+        let mut rings = Vec::new();
+        let mut outer_radius = self.outer_radius;
+        for ring in 0..self.ring_count {
+            if ring > 0 && outer_radius <= self.inner_radius {
+                break;
+            }
+            let remaining = (self.ring_count - ring) as f32;
+            let target_ratio =
+                (outer_radius - (outer_radius - self.inner_radius) / remaining) / outer_radius;
+            let (omen_vfx_path, ratio) = OMENS
+                .iter()
+                .min_by(|(_, r1), (_, r2)| {
+                    (r1 - target_ratio)
+                        .abs()
+                        .total_cmp(&(r2 - target_ratio).abs())
+                })
+                .copied()
+                .unwrap_or(OMENS[0]);
+            let inner_radius = outer_radius * ratio;
+            rings.push((inner_radius, outer_radius, omen_vfx_path));
+            outer_radius = inner_radius;
+        }
+        rings
+    }
+
+    fn ring_time(&self, ring: u32) -> f32 {
+        self.time_to_first_ring + ring as f32 * self.ring_interval
+    }
+}
+
+pub fn create_systems(world: &World) {
+    world
+        .system::<(&Mechanic, &mut Donut, &Position, &Rotation, &Party)>()
+        .term_at(4)
+        .up()
+        .each_iter(|it, index, (mechanic, donut, position, rotation, party)| {
+            let entity = it.entity(index);
+            let world = &it.world();
+
+            // Send the next ring's omen vfx
+            if donut.omen.is_none() {
+                let (_, outer_radius, omen_vfx_path) = donut.rings[donut.next_ring as usize];
+                let vfx_id = Uuid::new_v4().as_u128();
+                donut.omen = Some(*world.entity().child_of(entity).set(Vfx { id: vfx_id }));
+                broadcast_play_static_vfx(
+                    world,
+                    &party.id,
+                    PlayStaticVfxPayload {
+                        id: vfx_id,
+                        vfx_path: omen_vfx_path.to_string(),
+                        is_omen: true,
+                        world_position_x: position.x,
+                        world_position_y: position.y,
+                        world_position_z: position.z,
+                        rotation: rotation.value,
+                        scale_x: Some(outer_radius),
+                        scale_y: Some(outer_radius),
+                        scale_z: Some(outer_radius),
+                    },
+                );
+            }
+
+            donut.elapsed_time += it.delta_time();
+
+            if donut.elapsed_time < donut.ring_time(donut.next_ring) {
+                return;
+            }
+
+            // Go off
+            if let Some(omen) = donut.omen.take() {
+                omen.entity_view(world).destruct();
+            }
+            broadcast_play_actor_vfx_on_position(
+                world,
+                &party.id,
+                PlayActorVfxOnPositionPayload {
+                    vfx_path: donut.attack_vfx_path.clone(),
+                    world_position_x: position.x,
+                    world_position_y: position.y,
+                    world_position_z: position.z,
+                    rotation: rotation.value,
+                },
+            );
+
+            let (inner_radius, outer_radius, _) = donut.rings[donut.next_ring as usize];
+            let extent = entity.try_get::<&VerticalExtent>(|v| *v);
+            if let Some(pc) = find_party_container(world, &party.id) {
+                pc.each_child(|c| {
+                    if c.has(Stale::id()) {
+                        return;
+                    }
+                    c.try_get::<(&Player, &Position, &State)>(|(pl, p, s)| {
+                        if s.is_alive
+                            && is_in_donut(position, inner_radius, outer_radius, extent.as_ref(), p)
+                        {
+                            info!(
+                                mechanic.request_id,
+                                pl.content_id, donut.next_ring, "Donut hit player"
+                            );
+                            apply_condition(
+                                &c,
+                                donut.condition as u128,
+                                donut.condition,
+                                donut.condition_duration,
+                                false,
+                            );
+                        }
+                    });
+                });
+            }
+
+            donut.next_ring += 1;
+            if donut.next_ring as usize >= donut.rings.len() {
+                info!(
+                    mechanic.request_id,
+                    mechanic.mechanic_id, party.id, "Completing Mechanic"
+                );
+                entity.remove(Donut::id());
+            }
+        });
+}
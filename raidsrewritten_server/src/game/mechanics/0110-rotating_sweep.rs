@@ -0,0 +1,272 @@
+use crate::{
+    game::{
+        components::*,
+        condition::{self, apply_condition},
+        utils::*,
+    },
+    webserver::{message::RunMechanicCommandPayload, network_mechanic::NetworkMechanicCommand},
+};
+use flecs_ecs::prelude::*;
+use strum::IntoEnumIterator;
+use tracing::info;
+use uuid::Uuid;
+
+// A cone or line that starts at the mechanic's rotation, and after a delay sweeps around the mechanic's position
+// at a fixed angular speed for a duration. It's hit tested every tick against the whole arc it swept over,
+// and each player is only hit once.
+// Clients are sent the parameters once to show it themselves, and told when it stops.
+//
+// Configured through the extra data as
+// "shape,size,angle_or_width,angular_speed,delay,duration,condition,condition_duration",
+// where shape is "cone" (size is the radius, and the total angle in degrees follows it)
+// or "line" (size is the length, and the width follows it). angular_speed is in degrees per second,
+// turning towards increasing rotation values when positive, and condition is a Condition number.
+// Any missing or invalid value is left at its default.
+//
+// The start command's extra data is "id,shape,size,angle_or_width,angular_speed,delay,duration",
+// with every angle in radians like the rotation, so a cone's angle is in radians and angular_speed in radians per second. The stop command's extra data is the id.
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum SweepShape {
+    Cone,
+    Line,
+}
+
+#[derive(Component, Debug)]
+pub struct RotatingSweep {
+    // Settings
+    id: u128,
+    shape: SweepShape,
+    size: f32,
+    angle_or_width: f32,
+    angular_speed_degrees: f32,
+    delay: f32,
+    duration: f32,
+    condition: condition::Condition,
+    condition_duration: f32,
+    // Runtime
+    started: bool,
+    elapsed_time: f32,
+    hit: Vec<Entity>,
+}
+
+pub fn create_mechanic(entity: EntityView<'_>) -> EntityView<'_> {
+    let mut sweep = RotatingSweep {
+        // Settings
+        id: Uuid::new_v4().as_u128(),
+        shape: SweepShape::Cone,
+        size: 40.0,
+        angle_or_width: 30.0,
+        angular_speed_degrees: 30.0,
+        delay: 3.0,
+        duration: 8.0,
+        condition: condition::Condition::Stun,
+        condition_duration: 10.0,
+        // Runtime
+        started: false,
+        elapsed_time: 0.0,
+        hit: Vec::new(),
+    };
+
+    entity.try_get::<&ExtraMechanicData>(|ed| {
+        let values: Vec<&str> = ed.value.split(',').map(str::trim).collect();
+        let number = |i: usize| {
+            values
+                .get(i)
+                .and_then(|v| v.parse::<f32>().ok())
+                .filter(|v| v.is_finite())
+        };
+        if values.first() == Some(&"line") {
+            sweep.shape = SweepShape::Line;
+            sweep.angle_or_width = 6.0;
+        }
+        if let Some(s) = number(1)
+            && s > 0.0
+        {
+            sweep.size = s;
+        }
+        if let Some(a) = number(2)
+            && a > 0.0
+        {
+            sweep.angle_or_width = a;
+        }
+        if let Some(s) = number(3) {
+            sweep.angular_speed_degrees = s;
+        }
+        if let Some(d) = number(4)
+            && d >= 0.0
+        {
+            sweep.delay = d;
+        }
+        if let Some(d) = number(5)
+            && d >= 0.0
+        {
+            sweep.duration = d;
+        }
+        if let Some(c) = number(6)
+            && let Some(c) = condition::Condition::iter().find(|v| *v as u32 as f32 == c)
+            && c != condition::Condition::None
+        {
+            sweep.condition = c;
+        }
+        if let Some(d) = number(7)
+            && d > 0.0
+        {
+            sweep.condition_duration = d;
+        }
+    });
+
+    entity.set(sweep)
+}
+
+impl RotatingSweep {
+    fn rotation_at(&self, start_rotation: f32, elapsed_time: f32) -> f32 {
+        let sweep_time = (elapsed_time - self.delay).clamp(0.0, self.duration);
+        start_rotation + self.angular_speed_degrees.to_radians() * sweep_time
+    }
+
+    fn is_hit(
+        &self,
+        center: &Position,
+        start_rotation: f32,
+        end_rotation: f32,
+        extent: Option<&VerticalExtent>,
+        point: &Position,
+    ) -> bool {
+        match self.shape {
+            SweepShape::Cone => is_in_swept_cone(
+                center,
+                self.size,
+                self.angle_or_width / 2.0,
+                start_rotation,
+                end_rotation,
+                extent,
+                point,
+            ),
+            SweepShape::Line => is_in_swept_line(
+                center,
+                self.size,
+                self.angle_or_width / 2.0,
+                start_rotation,
+                end_rotation,
+                extent,
+                point,
+            ),
+        }
+    }
+}
+
+pub fn create_systems(world: &World) {
+    world
+        .system::<(&Mechanic, &mut RotatingSweep, &Position, &Rotation, &Party)>()
+        .term_at(4)
+        .up()
+        .each_iter(|it, index, (mechanic, sweep, position, rotation, party)| {
+            let entity = it.entity(index);
+            let world = &it.world();
+
+            if !sweep.started {
+                sweep.started = true;
+                let (shape, angle_or_width) = match sweep.shape {
+                    SweepShape::Cone => ("cone", sweep.angle_or_width.to_radians()),
+                    SweepShape::Line => ("line", sweep.angle_or_width),
+                };
+                broadcast_run_mechanic_command(
+                    world,
+                    &party.id,
+                    RunMechanicCommandPayload {
+                        mechanic_command_id: NetworkMechanicCommand::RotatingSweepStart as i32,
+                        world_position_x: Some(position.x),
+                        world_position_y: Some(position.y),
+                        world_position_z: Some(position.z),
+                        rotation: Some(rotation.value),
+                        extra_data: Some(format!(
+                            "{},{},{},{},{},{},{}",
+                            sweep.id,
+                            shape,
+                            sweep.size,
+                            angle_or_width,
+                            sweep.angular_speed_degrees.to_radians(),
+                            sweep.delay,
+                            sweep.duration
+                        )),
+                    },
+                );
+                return;
+            }
+
+            let previous_time = sweep.elapsed_time;
+            sweep.elapsed_time += it.delta_time();
+
+            if sweep.elapsed_time < sweep.delay {
+                return;
+            }
+
+            // Hit test against everything swept over since the last tick
+            let start_rotation = sweep.rotation_at(rotation.value, previous_time);
+            let end_rotation = sweep.rotation_at(rotation.value, sweep.elapsed_time);
+            let extent = entity.try_get::<&VerticalExtent>(|v| *v);
+            let mut hits: Vec<Entity> = Vec::new();
+            if let Some(pc) = find_party_container(world, &party.id) {
+                pc.each_child(|c| {
+                    if c.has(Stale::id()) || sweep.hit.contains(&c) {
+                        return;
+                    }
+                    c.try_get::<(&Player, &Position, &State)>(|(pl, p, s)| {
+                        if s.is_alive
+                            && sweep.is_hit(
+                                position,
+                                start_rotation,
+                                end_rotation,
+                                extent.as_ref(),
+                                p,
+                            )
+                        {
+                            info!(mechanic.request_id, pl.content_id, "Sweep hit player");
+                            hits.push(*c);
+                        }
+                    });
+                });
+            }
+            for player in hits {
+                sweep.hit.push(player);
+                apply_condition(
+                    &player.entity_view(world),
+                    sweep.condition as u128,
+                    sweep.condition,
+                    sweep.condition_duration,
+                    false,
+                );
+            }
+
+            if sweep.elapsed_time >= sweep.delay + sweep.duration {
+                info!(
+                    mechanic.request_id,
+                    mechanic.mechanic_id, party.id, "Completing Mechanic"
+                );
+                entity.remove(RotatingSweep::id());
+            }
+        });
+}
+
+pub fn create_observers(world: &World) {
+    // Tell clients to stop showing the sweep, whether it finished or was cleared
+    world
+        .observer::<flecs::OnRemove, (&RotatingSweep, &Party)>()
+        .term_at(1)
+        .up()
+        .each_iter(|it, _index, (sweep, party)| {
+            if !sweep.started {
+                return;
+            }
+            broadcast_run_mechanic_command(
+                &it.world(),
+                &party.id,
+                RunMechanicCommandPayload {
+                    mechanic_command_id: NetworkMechanicCommand::RotatingSweepStop as i32,
+                    extra_data: Some(sweep.id.to_string()),
+                    ..Default::default()
+                },
+            );
+        });
+}
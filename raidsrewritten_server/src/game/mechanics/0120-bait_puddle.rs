@@ -0,0 +1,255 @@
+use crate::{
+    game::{
+        components::*,
+        condition::{self, apply_condition},
+        utils::*,
+    },
+    webserver::message::*,
+};
+use flecs_ecs::prelude::*;
+use rand::seq::IndexedRandom;
+use strum::IntoEnumIterator;
+use tracing::info;
+use uuid::Uuid;
+
+// Puddles baited by random players. Where each living target stands at bait time is recorded, and a puddle
+// is placed there. When the puddle resolves, whoever is standing in it then gets the condition,
+// so targets have to drop their puddles away from the group and then move out.
+//
+// Configured through the extra data as "target_count,radius,time_to_bait,resolve_delay,condition,condition_duration",
+// where condition is a Condition number. Any missing or invalid value is left at its default.
+
+#[derive(Component, Debug)]
+pub struct BaitPuddle {
+    // Settings
+    target_count: usize,
+    radius: f32,
+    time_to_bait: f32,
+    resolve_delay: f32,
+    condition: condition::Condition,
+    condition_duration: f32,
+    bait_vfx_path: String,
+    puddle_vfx_path: String,
+    attack_vfx_path: String,
+    // Runtime
+    // Each puddle's position and vfx entity
+    puddles: Option<Vec<(Position, Entity)>>,
+}
+
+pub fn create_mechanic(entity: EntityView<'_>) -> EntityView<'_> {
+    let mut bait_puddle = BaitPuddle {
+        // Settings
+        target_count: 8,
+        radius: 5.0,
+        time_to_bait: 5.0,
+        resolve_delay: 3.0,
+        condition: condition::Condition::Stun,
+        condition_duration: 10.0,
+        bait_vfx_path: "vfx/lockon/eff/target_ae_s6k1.avfx".to_string(),
+        puddle_vfx_path: "vfx/omen/eff/general_1bf.avfx".to_string(),
+        attack_vfx_path: "vfx/monster/gimmick2/eff/f1bz_b0_g02c0i.avfx".to_string(),
+        // Runtime
+        puddles: None,
+    };
+
+    entity.try_get::<&ExtraMechanicData>(|ed| {
+        let values: Vec<Option<f32>> = ed
+            .value
+            .split(',')
+            .map(|v| v.trim().parse::<f32>().ok().filter(|v| v.is_finite()))
+            .collect();
+        if let Some(Some(n)) = values.first()
+            && *n >= 0.0
+        {
+            bait_puddle.target_count = *n as usize;
+        }
+        if let Some(Some(r)) = values.get(1)
+            && *r > 0.0
+        {
+            bait_puddle.radius = *r;
+        }
+        if let Some(Some(t)) = values.get(2)
+            && *t >= 0.0
+        {
+            bait_puddle.time_to_bait = *t;
+        }
+        if let Some(Some(d)) = values.get(3)
+            && *d >= 0.0
+        {
+            bait_puddle.resolve_delay = *d;
+        }
+        if let Some(Some(c)) = values.get(4)
+            && let Some(c) = condition::Condition::iter().find(|v| *v as u32 as f32 == *c)
+            && c != condition::Condition::None
+        {
+            bait_puddle.condition = c;
+        }
+        if let Some(Some(d)) = values.get(5)
+            && *d > 0.0
+        {
+            bait_puddle.condition_duration = *d;
+        }
+    });
+
+    entity.set(bait_puddle)
+}
+
+pub fn create_systems(world: &World) {
+    world
+        .system::<(&Mechanic, &mut BaitPuddle, &Party)>()
+        .term_at(2)
+        .up()
+        .each_iter(|it, index, (mechanic, bait_puddle, party)| {
+            let entity = it.entity(index);
+            let world = &it.world();
+
+            if !entity.has(Targets::id()) {
+                // Assign targets
+                let mut target_players: Vec<Entity> = Vec::new();
+                let mut targets: Vec<u64> = Vec::new();
+
+                if let Some(pc) = find_party_container(world, &party.id) {
+                    let mut players: Vec<Entity> = Vec::new();
+                    pc.each_child(|c| {
+                        if c.has(Stale::id()) {
+                            return;
+                        }
+                        c.try_get::<(&Player, &State)>(|(_, s)| {
+                            if s.is_alive {
+                                players.push(*c);
+                            }
+                        });
+                    });
+
+                    target_players.extend(
+                        players.choose_multiple(&mut rand::rng(), bait_puddle.target_count),
+                    );
+                    for target in &target_players {
+                        target.entity_view(world).try_get::<&Player>(|p| {
+                            targets.push(p.content_id);
+                        });
+                    }
+                }
+
+                entity.set(Targets {
+                    player_entities: target_players,
+                });
+
+                // Send bait vfx
+                broadcast_play_actor_vfx_on_target(
+                    world,
+                    &party.id,
+                    PlayActorVfxOnTargetPayload {
+                        vfx_path: bait_puddle.bait_vfx_path.clone(),
+                        content_id_targets: targets,
+                        ..Default::default()
+                    },
+                );
+                return;
+            }
+
+            bait_puddle.time_to_bait -= it.delta_time();
+
+            if bait_puddle.time_to_bait > 0.0 {
+                return;
+            }
+
+            let Some(puddles) = &mut bait_puddle.puddles else {
+                // Bait, recording where each living target is standing
+                let mut puddles: Vec<(Position, Entity)> = Vec::new();
+                let targets = entity
+                    .try_get::<&Targets>(|t| t.player_entities.clone())
+                    .unwrap_or_default();
+                for target in targets {
+                    let Some(target) = get_entity_view(&target, world) else {
+                        continue;
+                    };
+                    if target.has(Stale::id()) {
+                        continue;
+                    }
+                    let Some(position) = target
+                        .try_get::<(&Position, &State)>(|(p, s)| s.is_alive.then_some(*p))
+                        .flatten()
+                    else {
+                        continue;
+                    };
+
+                    // Place a puddle there
+                    let vfx_id = Uuid::new_v4().as_u128();
+                    puddles.push((
+                        position,
+                        *world.entity().child_of(entity).set(Vfx { id: vfx_id }),
+                    ));
+                    broadcast_play_static_vfx(
+                        world,
+                        &party.id,
+                        PlayStaticVfxPayload {
+                            id: vfx_id,
+                            vfx_path: bait_puddle.puddle_vfx_path.clone(),
+                            is_omen: true,
+                            world_position_x: position.x,
+                            world_position_y: position.y,
+                            world_position_z: position.z,
+                            rotation: 0.0,
+                            scale_x: Some(bait_puddle.radius),
+                            scale_y: Some(bait_puddle.radius),
+                            scale_z: Some(bait_puddle.radius),
+                        },
+                    );
+                }
+                bait_puddle.puddles = Some(puddles);
+                return;
+            };
+
+            bait_puddle.resolve_delay -= it.delta_time();
+
+            if bait_puddle.resolve_delay > 0.0 {
+                return;
+            }
+
+            // Resolve against whoever is standing in each puddle now
+            let extent = entity.try_get::<&VerticalExtent>(|v| *v);
+            for (puddle, vfx) in puddles.drain(..) {
+                vfx.entity_view(world).destruct();
+                broadcast_play_actor_vfx_on_position(
+                    world,
+                    &party.id,
+                    PlayActorVfxOnPositionPayload {
+                        vfx_path: bait_puddle.attack_vfx_path.clone(),
+                        world_position_x: puddle.x,
+                        world_position_y: puddle.y,
+                        world_position_z: puddle.z,
+                        rotation: 0.0,
+                    },
+                );
+
+                if let Some(pc) = find_party_container(world, &party.id) {
+                    pc.each_child(|c| {
+                        if c.has(Stale::id()) {
+                            return;
+                        }
+                        c.try_get::<(&Player, &Position, &State)>(|(pl, p, s)| {
+                            if s.is_alive
+                                && is_in_circle(&puddle, bait_puddle.radius, extent.as_ref(), p)
+                            {
+                                info!(mechanic.request_id, pl.content_id, "Puddle hit player");
+                                apply_condition(
+                                    &c,
+                                    bait_puddle.condition as u128,
+                                    bait_puddle.condition,
+                                    bait_puddle.condition_duration,
+                                    false,
+                                );
+                            }
+                        });
+                    });
+                }
+            }
+
+            info!(
+                mechanic.request_id,
+                mechanic.mechanic_id, party.id, "Completing Mechanic"
+            );
+            entity.remove(BaitPuddle::id());
+        });
+}
@@ -37,7 +37,7 @@ pub fn create_mechanic(entity: EntityView<'_>) -> EntityView<'_> {
     })
 }
 
-fn handle_stacks(targets: &mut Vec<Target>) -> Vec<Target> {
+fn handle_stacks(targets: &mut [Target]) -> Vec<Target> {
     let mut origins: Vec<Target> = Vec::new();
     for t in targets.iter().rev() {
         if origins.len() < 2 {
@@ -67,7 +67,7 @@ fn handle_stacks(targets: &mut Vec<Target>) -> Vec<Target> {
     origins
 }
 
-fn handle_cones(targets: &mut Vec<Target>, position: &Position) -> Vec<Target> {
+fn handle_cones(targets: &mut [Target], position: &Position) -> Vec<Target> {
     let mut origins: Vec<Target> = Vec::new();
     for t in targets.iter() {
         if origins.len() < 2 {
@@ -101,7 +101,7 @@ fn handle_cones(targets: &mut Vec<Target>, position: &Position) -> Vec<Target> {
     origins
 }
 
-fn handle_mechanics(targets: &mut Vec<Target>, position: &Position) -> MechanicResults {
+fn handle_mechanics(targets: &mut [Target], position: &Position) -> MechanicResults {
     let stack_result = handle_stacks(targets);
     let cone_result = handle_cones(targets, position);
 
@@ -114,6 +114,8 @@ fn handle_mechanics(targets: &mut Vec<Target>, position: &Position) -> MechanicR
 pub fn create_systems(world: &World) {
     world
         .system::<(&Mechanic, &TeaFireTornado1, &Position, &Party)>()
+        .term_at(3)
+        .up()
         .each_iter(|it, index, (mechanic, fire_tornado, position, party)| {
             let entity = it.entity(index);
 
@@ -122,6 +124,9 @@ pub fn create_systems(world: &World) {
             if let Some(pc) = find_party_container(&world, &party.id) {
                 let mut targets: Vec<Target> = Vec::new();
                 pc.each_child(|c| {
+                    if c.has(Stale::id()) {
+                        return;
+                    }
                     c.try_get::<(&Player, &Position, &State)>(|(pl, p, s)| {
                         if !s.is_alive {
                             return;
@@ -143,40 +148,33 @@ pub fn create_systems(world: &World) {
 
                 targets.sort_unstable_by(|a, b| a.distance.total_cmp(&b.distance));
 
-                let mechanic_results = handle_mechanics(&mut targets, &position);
-
-                let io = get_socket_io(&it.world());
-                pc.each_child(|c| {
-                    c.try_get::<&Socket>(|s| {
-                        for t in &mechanic_results.cone_origins {
-                            let r = vector_to_rotation(
-                                t.position.x - position.x,
-                                t.position.z - position.z,
-                            );
-                            send_play_actor_vfx_on_position(
-                                io.clone(),
-                                s.id,
-                                PlayActorVfxOnPositionPayload {
-                                    vfx_path: fire_tornado.cone_vfx.clone(),
-                                    world_position_x: position.x,
-                                    world_position_y: position.y,
-                                    world_position_z: position.z,
-                                    rotation: r,
-                                },
-                            );
-                        }
+                let mechanic_results = handle_mechanics(&mut targets, position);
+
+                for t in &mechanic_results.cone_origins {
+                    let r =
+                        vector_to_rotation(t.position.x - position.x, t.position.z - position.z);
+                    broadcast_play_actor_vfx_on_position(
+                        &world,
+                        &party.id,
+                        PlayActorVfxOnPositionPayload {
+                            vfx_path: fire_tornado.cone_vfx.clone(),
+                            world_position_x: position.x,
+                            world_position_y: position.y,
+                            world_position_z: position.z,
+                            rotation: r,
+                        },
+                    );
+                }
 
-                        send_play_actor_vfx_on_target(
-                            io.clone(),
-                            s.id,
-                            PlayActorVfxOnTargetPayload {
-                                vfx_path: fire_tornado.stack_vfx.clone(),
-                                content_id_targets: mechanic_results.stack_origins.clone(),
-                                ..Default::default()
-                            },
-                        );
-                    });
-                });
+                broadcast_play_actor_vfx_on_target(
+                    &world,
+                    &party.id,
+                    PlayActorVfxOnTargetPayload {
+                        vfx_path: fire_tornado.stack_vfx.clone(),
+                        content_id_targets: mechanic_results.stack_origins,
+                        ..Default::default()
+                    },
+                );
 
                 let mut to_punish: bool = false;
                 for t in targets {
@@ -1,290 +1,69 @@
-use crate::{
-    game::{
-        components::*,
-        condition::{self, apply_condition},
-        utils::*,
-    },
-    webserver::message::*,
+use crate::game::{
+    components::*,
+    condition::{self, apply_condition},
+    mechanics::m0040_tower::Tower,
 };
-use distances::vectors::euclidean;
 use flecs_ecs::prelude::*;
-use std::collections::HashMap;
-use tracing::info;
-use uuid::Uuid;
 
-#[derive(Component, Debug)]
-pub struct HawkBlasterTower {
-    time_to_snapshot: f32,
-    attack_delay: f32,
-    effect_delay: f32,
-    failure_attack_delay: f32,
-    failure_effect_delay: f32,
-    radius: f32,
-    tower_vfx: String,
-    attack_vfx: String,
-    failure_attack_vfx: String,
-    phase: Phase,
-}
-
-#[derive(Debug)]
-enum Phase {
-    Omen,
-    Snapshot,
-    Attack,
-    Failure,
-    FailureAttack,
-}
+// A tower that needs 2 or more soakers. Soaking gives Fire Resistance Down, and soaking while already
+// having it stuns and pacifies. If too few players soak, the whole party gets Hysteria and Pacify.
 
 pub fn create_mechanic(entity: EntityView<'_>) -> EntityView<'_> {
-    entity.set(HawkBlasterTower {
-        time_to_snapshot: 2.0,
-        attack_delay: 0.2,
-        effect_delay: 0.2,
-        failure_attack_delay: 1.0,
-        failure_effect_delay: 0.1,
-        radius: 4.0,
-        tower_vfx: "vfx/omen/eff/general_trap_o2x.avfx".to_string(),
-        attack_vfx: "vfx/monster/gimmick2/eff/d2ac2_b4_g01c0c.avfx".to_string(),
-        failure_attack_vfx: "vfx/monster/d1025/eff/d1025_sp12_bunsan_zentai_t0s.avfx".to_string(),
-        phase: Phase::Omen,
-    })
+    let mut tower = Tower::default();
+    tower.time_to_snapshot = 2.0;
+    tower.radius = 4.0;
+    tower.min_soakers = 2;
+    tower.max_soakers = usize::MAX;
+    tower.vulnerability = Some(condition::Condition::FireResistanceDown);
+    tower.on_soak = Some(on_soak);
+    tower.on_failure = Some(on_failure);
+    entity.set(tower)
 }
 
-pub fn create_systems(world: &World) {
-    world
-        .system::<(
-            &Mechanic,
-            &mut HawkBlasterTower,
-            &Position,
-            &Rotation,
-            &Party,
-        )>()
-        .each_iter(|it, index, (mechanic, tower, position, rotation, party)| {
-            let entity = it.entity(index);
-            let world = &it.world();
-
-            match tower.phase {
-                Phase::Omen => {
-                    // Send all players the tower vfx
-                    if !entity.has(Vfx::id()) {
-                        let vfx_id = Uuid::new_v4().as_u128();
-                        entity.set(Vfx { id: vfx_id });
-
-                        if let Some(pc) = find_party_container(world, &party.id) {
-                            let io = get_socket_io(world);
-                            pc.each_child(|c| {
-                                c.try_get::<(&Socket, &Player)>(|(s, _)| {
-                                    send_play_static_vfx(
-                                        io.clone(),
-                                        s.id,
-                                        PlayStaticVfxPayload {
-                                            id: vfx_id,
-                                            vfx_path: tower.tower_vfx.clone(),
-                                            is_omen: true,
-                                            world_position_x: position.x,
-                                            world_position_y: position.y,
-                                            world_position_z: position.z,
-                                            rotation: rotation.value,
-                                            scale_x: Some(tower.radius),
-                                            scale_y: Some(tower.radius),
-                                            scale_z: Some(tower.radius),
-                                        },
-                                    );
-                                });
-                            });
-                        }
-                    }
-
-                    tower.time_to_snapshot -= it.delta_time();
-                    if tower.time_to_snapshot > 0.0 {
-                        return;
-                    }
-
-                    // Snapshot
-                    entity.remove(Vfx::id());
-
-                    let mut affects: HashMap<Entity, u8> = HashMap::new();
-
-                    if let Some(pc) = find_party_container(world, &party.id) {
-                        pc.each_child(|c1| {
-                            c1.try_get::<(&Player, &Position, &State)>(|(_, p, s)| {
-                                if !s.is_alive {
-                                    return;
-                                }
-
-                                let p1 = [position.x, position.z];
-                                let p2 = [p.x, p.z];
-                                let distance: f32 = euclidean(&p1, &p2);
-
-                                if distance <= tower.radius {
-                                    let mut has_vuln = false;
-                                    c1.each_child(|c2| {
-                                        c2.try_get::<&Condition>(|condition| {
-                                            if condition.condition
-                                                == condition::Condition::FireResistanceDown
-                                            {
-                                                has_vuln = true;
-                                            }
-                                        });
-                                    });
-                                    add_affect(&mut affects, &c1, if has_vuln { 2 } else { 1 });
-                                }
-                            });
-                        });
-                    }
-
-                    entity.set(Affects {
-                        player_entities: affects,
-                    });
-
-                    tower.phase = Phase::Snapshot;
-                    return;
-                }
-
-                Phase::Snapshot => {
-                    tower.attack_delay -= it.delta_time();
-                    if tower.attack_delay > 0.0 {
-                        return;
-                    }
-
-                    if let Some(pc) = find_party_container(world, &party.id) {
-                        let io = get_socket_io(world);
-                        pc.each_child(|c| {
-                            c.try_get::<&Socket>(|s| {
-                                // Play attack vfx
-                                send_play_actor_vfx_on_position(
-                                    io.clone(),
-                                    s.id,
-                                    PlayActorVfxOnPositionPayload {
-                                        vfx_path: tower.attack_vfx.clone(),
-                                        world_position_x: position.x,
-                                        world_position_y: position.y,
-                                        world_position_z: position.z,
-                                        rotation: rotation.value,
-                                    },
-                                );
-                            });
-                        });
-                    }
-
-                    tower.phase = Phase::Attack;
-                    return;
-                }
-
-                Phase::Attack => {
-                    tower.effect_delay -= it.delta_time();
-                    if tower.effect_delay > 0.0 {
-                        return;
-                    }
-
-                    let mut affect_count = 0;
-                    entity.try_get::<&Affects>(|a| {
-                        affect_count = a.player_entities.len();
-                        for (e, &count) in &a.player_entities {
-                            let player = e.entity_view(world);
-                            apply_condition(
-                                &player,
-                                condition::Condition::FireResistanceDown as u128,
-                                condition::Condition::FireResistanceDown,
-                                15.0,
-                                false,
-                            );
-                            if count > 1 {
-                                apply_condition(
-                                    &player,
-                                    condition::Condition::Stun as u128,
-                                    condition::Condition::Stun,
-                                    15.0,
-                                    false,
-                                );
-                                apply_condition(
-                                    &player,
-                                    condition::Condition::Pacify as u128,
-                                    condition::Condition::Pacify,
-                                    30.0,
-                                    false,
-                                );
-                            }
-                        }
-                    });
-
-                    if affect_count < 2 {
-                        tower.phase = Phase::Failure;
-                        return;
-                    }
-                }
-
-                Phase::Failure => {
-                    tower.failure_attack_delay -= it.delta_time();
-                    if tower.failure_attack_delay > 0.0 {
-                        return;
-                    }
-
-                    if let Some(pc) = find_party_container(world, &party.id) {
-                        let io = get_socket_io(world);
-                        pc.each_child(|c| {
-                            c.try_get::<&Socket>(|s| {
-                                // Play attack vfx
-                                send_play_actor_vfx_on_position(
-                                    io.clone(),
-                                    s.id,
-                                    PlayActorVfxOnPositionPayload {
-                                        vfx_path: tower.failure_attack_vfx.clone(),
-                                        world_position_x: position.x,
-                                        world_position_y: position.y,
-                                        world_position_z: position.z,
-                                        rotation: rotation.value,
-                                    },
-                                );
-                            });
-                        });
-                    }
-
-                    tower.phase = Phase::FailureAttack;
-                    return;
-                }
-
-                Phase::FailureAttack => {
-                    tower.failure_effect_delay -= it.delta_time();
-                    if tower.failure_effect_delay > 0.0 {
-                        return;
-                    }
-
-                    if let Some(pc) = find_party_container(world, &party.id) {
-                        pc.each_child(|c| {
-                            c.try_get::<(&Player, &State)>(|(_, s)| {
-                                if !s.is_alive {
-                                    return;
-                                }
-                                let player = c.entity_view(world);
-                                apply_condition(
-                                    &player,
-                                    condition::Condition::Hysteria as u128,
-                                    condition::Condition::Hysteria,
-                                    15.0,
-                                    false,
-                                )
-                                .entity_view(world)
-                                .set(conditions::Hysteria {
-                                    redirection_interval: 5.0,
-                                });
-                                apply_condition(
-                                    &player,
-                                    condition::Condition::Pacify as u128,
-                                    condition::Condition::Pacify,
-                                    30.0,
-                                    false,
-                                );
-                            });
-                        });
-                    }
-                }
-            }
+fn on_soak(player: &EntityView<'_>, affect_count: u8) {
+    apply_condition(
+        player,
+        condition::Condition::FireResistanceDown as u128,
+        condition::Condition::FireResistanceDown,
+        15.0,
+        false,
+    );
+    if affect_count > 1 {
+        apply_condition(
+            player,
+            condition::Condition::Stun as u128,
+            condition::Condition::Stun,
+            15.0,
+            false,
+        );
+        apply_condition(
+            player,
+            condition::Condition::Pacify as u128,
+            condition::Condition::Pacify,
+            30.0,
+            false,
+        );
+    }
+}
 
-            info!(
-                mechanic.request_id,
-                mechanic.mechanic_id, party.id, "Completing Mechanic"
-            );
-            entity.remove(HawkBlasterTower::id());
-        });
+fn on_failure(player: &EntityView<'_>) {
+    let world = player.world();
+    apply_condition(
+        player,
+        condition::Condition::Hysteria as u128,
+        condition::Condition::Hysteria,
+        15.0,
+        false,
+    )
+    .entity_view(world)
+    .set(conditions::Hysteria {
+        redirection_interval: 5.0,
+    });
+    apply_condition(
+        player,
+        condition::Condition::Pacify as u128,
+        condition::Condition::Pacify,
+        30.0,
+        false,
+    );
 }
@@ -16,6 +16,8 @@ pub fn create_mechanic(entity: EntityView<'_>) -> EntityView<'_> {
 pub fn create_systems(world: &World) {
     world
         .system::<(&Mechanic, &ExtraMechanicData, &Party)>()
+        .term_at(2)
+        .up()
         .with(BlasstyChargeHit)
         .each_iter(|it, index, (mechanic, extra_data, party)| {
             let entity = it.entity(index);
@@ -28,49 +30,45 @@ pub fn create_systems(world: &World) {
                 }
             }
 
-            if let Some(pc) = find_party_container(world, &party.id) {
-                pc.each_child(|c1| {
-                    c1.try_get::<&Player>(|p| {
-                        if targets.contains(&p.content_id) {
-                            let mut has_vuln = false;
-                            c1.each_child(|c2| {
-                                c2.try_get::<&Condition>(|condition| {
-                                    if condition.condition
-                                        == condition::Condition::FireResistanceDown
-                                    {
-                                        has_vuln = true;
-                                    }
-                                });
-                            });
-
-                            let player = c1;
-                            if has_vuln {
-                                apply_condition(
-                                    &player,
-                                    condition::Condition::Stun as u128,
-                                    condition::Condition::Stun,
-                                    15.0,
-                                    false,
-                                );
-                                apply_condition(
-                                    &player,
-                                    condition::Condition::Pacify as u128,
-                                    condition::Condition::Pacify,
-                                    30.0,
-                                    false,
-                                );
+            let party_container = find_party_container(world, &party.id).map(|pc| *pc);
+            for content_id in targets {
+                // Only players in the same party as the mechanic can be hit
+                if let Some(player) = find_player(world, content_id)
+                    && player.parent().map(|p| *p) == party_container
+                {
+                    let mut has_vuln = false;
+                    player.each_child(|c2| {
+                        c2.try_get::<&Condition>(|condition| {
+                            if condition.condition == condition::Condition::FireResistanceDown {
+                                has_vuln = true;
                             }
-
-                            apply_condition(
-                                &player,
-                                condition::Condition::FireResistanceDown as u128,
-                                condition::Condition::FireResistanceDown,
-                                15.0,
-                                false,
-                            );
-                        }
+                        });
                     });
-                });
+                    if has_vuln {
+                        apply_condition(
+                            &player,
+                            condition::Condition::Stun as u128,
+                            condition::Condition::Stun,
+                            15.0,
+                            false,
+                        );
+                        apply_condition(
+                            &player,
+                            condition::Condition::Pacify as u128,
+                            condition::Condition::Pacify,
+                            30.0,
+                            false,
+                        );
+                    }
+
+                    apply_condition(
+                        &player,
+                        condition::Condition::FireResistanceDown as u128,
+                        condition::Condition::FireResistanceDown,
+                        15.0,
+                        false,
+                    );
+                }
             }
 
             info!(
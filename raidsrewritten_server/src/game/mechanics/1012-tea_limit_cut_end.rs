@@ -12,6 +12,8 @@ pub fn create_mechanic(entity: EntityView<'_>) -> EntityView<'_> {
 pub fn create_systems(world: &World) {
     world
         .system::<(&Mechanic, &Party)>()
+        .term_at(1)
+        .up()
         .with(LimitCutEnd)
         .each_iter(|it, index, (mechanic, party)| {
             let entity = it.entity(index);
@@ -9,6 +9,7 @@ use tracing::warn;
 
 #[derive(Component, Debug)]
 pub struct TeaShanoa {
+    #[allow(dead_code)]
     pub visible: bool,
     pub navigation_markers: HashSet<u8>,
     pub absorbed_markers: HashSet<u8>,
@@ -30,6 +31,18 @@ struct FireTornado {
 
 const ARENA_CENTER: Vector3<f32> = Vector3::new(100.0, 0.0, 100.0);
 
+// Shanoa is parented to the party container, so only that party's children need to be checked
+pub fn find_shanoa<'a>(world: &WorldRef<'a>, party_id: &str) -> Option<EntityView<'a>> {
+    let pc = find_party_container(world, party_id)?;
+    let mut shanoa = None;
+    pc.each_child(|c| {
+        if shanoa.is_none() && c.has(TeaShanoa::id()) {
+            shanoa = Some(*c);
+        }
+    });
+    shanoa.and_then(|e| get_entity_view(&e, world))
+}
+
 pub fn create_mechanic(entity: EntityView<'_>) -> EntityView<'_> {
     entity.set(FireTornado {
         spawned_shanoa: false,
@@ -44,6 +57,8 @@ pub fn create_systems(world: &World) {
             &mut TeaShanoaTargetPosition,
             &Party,
         )>()
+        .term_at(3)
+        .up()
         .each_iter(|it, index, (shanoa, position, target_position, party)| {
             let entity = it.entity(index);
             let world = &it.world();
@@ -59,23 +74,15 @@ pub fn create_systems(world: &World) {
                 shanoa.navigation_markers.remove(&target_position.marker_id);
                 shanoa.absorbed_markers.insert(target_position.marker_id);
 
-                if let Some(pc) = find_party_container(world, &party.id) {
-                    let io = get_socket_io(world);
-                    pc.each_child(|c| {
-                        c.try_get::<(&Socket, &Player)>(|(s, _)| {
-                            send_run_mechanic_command(
-                                io.clone(),
-                                s.id,
-                                RunMechanicCommandPayload {
-                                    mechanic_command_id:
-                                        NetworkMechanicCommand::TeaShanoaAbsorbsMarker as i32,
-                                    extra_data: Some(target_position.marker_id.to_string()),
-                                    ..Default::default()
-                                },
-                            );
-                        });
-                    });
-                }
+                broadcast_run_mechanic_command(
+                    world,
+                    &party.id,
+                    RunMechanicCommandPayload {
+                        mechanic_command_id: NetworkMechanicCommand::TeaShanoaAbsorbsMarker as i32,
+                        extra_data: Some(target_position.marker_id.to_string()),
+                        ..Default::default()
+                    },
+                );
 
                 entity.remove(TeaShanoaTargetPosition::id());
             } else {
@@ -89,6 +96,8 @@ pub fn create_systems(world: &World) {
 
     world
         .system::<(&Mechanic, &mut FireTornado, &Position, &Party)>()
+        .term_at(3)
+        .up()
         .each_iter(|it, index, (mechanic, fire_tornado, position, party)| {
             if fire_tornado.spawned_shanoa {
                 return;
@@ -97,11 +106,7 @@ pub fn create_systems(world: &World) {
             let entity = it.entity(index);
             let world = &it.world();
 
-            if let Some(shanoa) = world
-                .query::<(&TeaShanoa, &Party)>()
-                .build()
-                .find(|(_, p)| p.id == party.id)
-            {
+            if let Some(shanoa) = find_shanoa(world, &party.id) {
                 warn!("Shanoa Entity already exists! Updating fire tornado position instead.");
                 shanoa.try_get::<&mut TeaShanoa>(|shanoa| {
                     let fire_tornado_entity = shanoa.fire_tornado.entity_view(world);
@@ -119,11 +124,14 @@ pub fn create_systems(world: &World) {
                     fire_tornado_position + distance_towards_center * towards_center;
                 let shanoa_rotation = vector_to_rotation(towards_center.x, towards_center.z);
 
+                let Some(party_container) = entity.parent() else {
+                    return;
+                };
                 let shanoa_entity = create_generic_mechanic(
                     world,
                     mechanic.request_id.clone(),
                     mechanic.mechanic_id,
-                    party.id.clone(),
+                    *party_container,
                 );
                 shanoa_entity
                     .set(TeaShanoa {
@@ -143,26 +151,18 @@ pub fn create_systems(world: &World) {
                         value: shanoa_rotation,
                     });
 
-                if let Some(pc) = find_party_container(world, &party.id) {
-                    let io = get_socket_io(world);
-                    pc.each_child(|c| {
-                        c.try_get::<(&Socket, &Player)>(|(s, _)| {
-                            send_run_mechanic_command(
-                                io.clone(),
-                                s.id,
-                                RunMechanicCommandPayload {
-                                    mechanic_command_id: NetworkMechanicCommand::TeaShowShanoa
-                                        as i32,
-                                    world_position_x: Some(shanoa_position.x),
-                                    world_position_y: Some(shanoa_position.y),
-                                    world_position_z: Some(shanoa_position.z),
-                                    rotation: Some(shanoa_rotation),
-                                    extra_data: None,
-                                },
-                            );
-                        });
-                    });
-                }
+                broadcast_run_mechanic_command(
+                    world,
+                    &party.id,
+                    RunMechanicCommandPayload {
+                        mechanic_command_id: NetworkMechanicCommand::TeaShowShanoa as i32,
+                        world_position_x: Some(shanoa_position.x),
+                        world_position_y: Some(shanoa_position.y),
+                        world_position_z: Some(shanoa_position.z),
+                        rotation: Some(shanoa_rotation),
+                        extra_data: None,
+                    },
+                );
             }
 
             fire_tornado.spawned_shanoa = true;
@@ -1,7 +1,7 @@
 use crate::{
     game::{
         components::*,
-        mechanics::m1020_tea_spawn_shanoa::{TeaShanoa, TeaShanoaTargetPosition},
+        mechanics::m1020_tea_spawn_shanoa::{TeaShanoa, TeaShanoaTargetPosition, find_shanoa},
         utils::*,
     },
     webserver::{message::RunMechanicCommandPayload, network_mechanic::NetworkMechanicCommand},
@@ -20,6 +20,8 @@ pub fn create_mechanic(entity: EntityView<'_>) -> EntityView<'_> {
 pub fn create_systems(world: &World) {
     world
         .system::<(&Mechanic, &Position, &Rotation, &ExtraMechanicData, &Party)>()
+        .term_at(4)
+        .up()
         .with(MoveShanoa)
         .each_iter(
             |it, index, (mechanic, position, rotation, extra_data, party)| {
@@ -29,46 +31,36 @@ pub fn create_systems(world: &World) {
                 let mut can_move = false;
                 let mut movement_speed = 0.0;
                 let mut rotation_speed = 0.0;
-                if let Ok(marker_id) = extra_data.value.parse::<u8>() {
-                    world
-                        .query::<(&mut TeaShanoa, &mut Rotation, &Party)>()
-                        .build()
-                        .each_entity(|e, (shanoa, r, p)| {
-                            if p.id != party.id {
-                                return;
-                            }
-                            if shanoa.navigation_markers.contains(&marker_id) {
-                                can_move = true;
-                                e.set(TeaShanoaTargetPosition {
-                                    value: Vector3::new(position.x, position.y, position.z),
-                                    marker_id,
-                                });
-                                r.value = rotation.value; // insta-set the rotation because this value doesn't really matter on the server
-                                movement_speed = shanoa.movement_speed;
-                                rotation_speed = shanoa.rotation_speed;
-                            }
-                        });
+                if let Ok(marker_id) = extra_data.value.parse::<u8>()
+                    && let Some(e) = find_shanoa(world, &party.id)
+                {
+                    e.try_get::<(&TeaShanoa, &mut Rotation)>(|(shanoa, r)| {
+                        if shanoa.navigation_markers.contains(&marker_id) {
+                            can_move = true;
+                            e.set(TeaShanoaTargetPosition {
+                                value: Vector3::new(position.x, position.y, position.z),
+                                marker_id,
+                            });
+                            r.value = rotation.value; // insta-set the rotation because this value doesn't really matter on the server
+                            movement_speed = shanoa.movement_speed;
+                            rotation_speed = shanoa.rotation_speed;
+                        }
+                    });
                 }
 
-                if can_move && let Some(pc) = find_party_container(world, &party.id) {
-                    let io = get_socket_io(world);
-                    pc.each_child(|c| {
-                        c.try_get::<(&Socket, &Player)>(|(s, _)| {
-                            send_run_mechanic_command(
-                                io.clone(),
-                                s.id,
-                                RunMechanicCommandPayload {
-                                    mechanic_command_id: NetworkMechanicCommand::TeaMoveShanoa
-                                        as i32,
-                                    world_position_x: Some(position.x),
-                                    world_position_y: Some(position.y),
-                                    world_position_z: Some(position.z),
-                                    rotation: Some(rotation.value),
-                                    extra_data: Some(format!("{movement_speed},{rotation_speed}")),
-                                },
-                            );
-                        });
-                    });
+                if can_move {
+                    broadcast_run_mechanic_command(
+                        world,
+                        &party.id,
+                        RunMechanicCommandPayload {
+                            mechanic_command_id: NetworkMechanicCommand::TeaMoveShanoa as i32,
+                            world_position_x: Some(position.x),
+                            world_position_y: Some(position.y),
+                            world_position_z: Some(position.z),
+                            rotation: Some(rotation.value),
+                            extra_data: Some(format!("{movement_speed},{rotation_speed}")),
+                        },
+                    );
                 }
 
                 info!(
@@ -1,5 +1,9 @@
 use crate::{
-    game::{components::*, mechanics::m1020_tea_spawn_shanoa::TeaShanoa, utils::*},
+    game::{
+        components::*,
+        mechanics::m1020_tea_spawn_shanoa::{TeaShanoa, find_shanoa},
+        utils::*,
+    },
     webserver::{message::RunMechanicCommandPayload, network_mechanic::NetworkMechanicCommand},
 };
 use flecs_ecs::prelude::*;
@@ -24,6 +28,8 @@ pub fn create_mechanic(entity: EntityView<'_>) -> EntityView<'_> {
 pub fn create_systems(world: &World) {
     world
         .system::<(&Mechanic, &mut FireTornadoAttackShanoa, &Position, &Party)>()
+        .term_at(3)
+        .up()
         .each_iter(|it, index, (mechanic, attack, position, party)| {
             let entity = it.entity(index);
             let world = &it.world();
@@ -31,26 +37,19 @@ pub fn create_systems(world: &World) {
             if !attack.attack_sent {
                 attack.attack_sent = true;
 
-                if let Some(pc) = find_party_container(world, &party.id) {
-                    let io = get_socket_io(world);
-                    pc.each_child(|c| {
-                        c.try_get::<(&Socket, &Player)>(|(s, _)| {
-                            send_run_mechanic_command(
-                                io.clone(),
-                                s.id,
-                                RunMechanicCommandPayload {
-                                    mechanic_command_id:
-                                        NetworkMechanicCommand::TeaFireTornadoAttackShanoa as i32,
-                                    extra_data: Some(format!(
-                                        "{},{}",
-                                        attack.omen_duration, attack.distance_threshold
-                                    )),
-                                    ..Default::default()
-                                },
-                            );
-                        });
-                    });
-                }
+                broadcast_run_mechanic_command(
+                    world,
+                    &party.id,
+                    RunMechanicCommandPayload {
+                        mechanic_command_id: NetworkMechanicCommand::TeaFireTornadoAttackShanoa
+                            as i32,
+                        extra_data: Some(format!(
+                            "{},{}",
+                            attack.omen_duration, attack.distance_threshold
+                        )),
+                        ..Default::default()
+                    },
+                );
             }
 
             attack.omen_duration -= it.delta_time();
@@ -60,44 +59,34 @@ pub fn create_systems(world: &World) {
             }
 
             // Attack Shanoa
-            world
-                .query::<(&TeaShanoa, &Position, &Party)>()
-                .build()
-                .each_entity(|e, (shanoa, shanoa_position, p)| {
-                    if p.id != party.id {
-                        return;
-                    }
+            if let Some(e) = find_shanoa(world, &party.id) {
+                let mut speeds = None;
+                e.try_get::<(&TeaShanoa, &Position)>(|(shanoa, shanoa_position)| {
                     let shanoa_position = Vector2::new(shanoa_position.x, shanoa_position.z);
                     let fire_tornado_position = Vector2::new(position.x, position.z);
                     if shanoa_position.metric_distance(&fire_tornado_position)
                         <= attack.distance_threshold
                     {
-                        // Destruct Shanoa entity
-                        e.destruct();
-
-                        if let Some(pc) = find_party_container(world, &party.id) {
-                            let io = get_socket_io(world);
-                            pc.each_child(|c| {
-                                c.try_get::<(&Socket, &Player)>(|(s, _)| {
-                                    send_run_mechanic_command(
-                                        io.clone(),
-                                        s.id,
-                                        RunMechanicCommandPayload {
-                                            mechanic_command_id:
-                                                NetworkMechanicCommand::TeaShanoaRunsAway as i32,
-                                            extra_data: Some(format!(
-                                                "{},{}",
-                                                shanoa.movement_speed, shanoa.rotation_speed
-                                            )),
-                                            ..Default::default()
-                                        },
-                                    );
-                                });
-                            });
-                        }
+                        speeds = Some((shanoa.movement_speed, shanoa.rotation_speed));
                     }
                 });
 
+                if let Some((movement_speed, rotation_speed)) = speeds {
+                    // Destruct Shanoa entity
+                    e.destruct();
+
+                    broadcast_run_mechanic_command(
+                        world,
+                        &party.id,
+                        RunMechanicCommandPayload {
+                            mechanic_command_id: NetworkMechanicCommand::TeaShanoaRunsAway as i32,
+                            extra_data: Some(format!("{movement_speed},{rotation_speed}")),
+                            ..Default::default()
+                        },
+                    );
+                }
+            }
+
             info!(
                 mechanic.request_id,
                 mechanic.mechanic_id, party.id, "Completing Mechanic"
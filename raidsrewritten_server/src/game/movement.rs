@@ -0,0 +1,320 @@
+use crate::game::components::*;
+use flecs_ecs::prelude::*;
+use std::time::{Duration, Instant};
+
+// Velocity is estimated from the UpdateStatus samples received within this window
+pub const VELOCITY_WINDOW: Duration = Duration::from_millis(500);
+// Extrapolation never predicts further than this past the last UpdateStatus,
+// so a player who stops sending updates doesn't keep drifting away
+pub const MAX_EXTRAPOLATION_TIME: Duration = Duration::from_millis(500);
+// Caps estimates from teleports or zoning, which aren't movement
+pub const MAX_ESTIMATED_SPEED: f32 = 20.0;
+// How far back positions are kept, for resolving mechanics against where players were when they went off
+pub const POSITION_HISTORY_WINDOW: Duration = Duration::from_secs(2);
+const MAX_POSITION_SAMPLES: usize = 64;
+// How far back MovementFlags changes are kept
+pub const MOVEMENT_FLAGS_WINDOW: Duration = Duration::from_secs(5);
+const MAX_MOVEMENT_FLAGS_SAMPLES: usize = 32;
+
+// Records a reported position and updates the player's estimated Velocity
+pub fn record_position_sample(player: &EntityView<'_>, position: Position, time: Instant) {
+    if !player.has(PositionHistory::id()) {
+        player.set(PositionHistory::default());
+    }
+
+    let velocity = player.get::<&mut PositionHistory>(|history| {
+        history.samples.push_back((time, position));
+        while history.samples.len() > MAX_POSITION_SAMPLES
+            || history
+                .samples
+                .front()
+                .is_some_and(|(t, _)| time.duration_since(*t) > POSITION_HISTORY_WINDOW)
+        {
+            history.samples.pop_front();
+        }
+        estimate_velocity(history)
+    });
+
+    player.set(velocity);
+}
+
+fn estimate_velocity(history: &PositionHistory) -> Velocity {
+    let Some((t2, p2)) = history.samples.back() else {
+        return Velocity::default();
+    };
+    let Some((t1, p1)) = history
+        .samples
+        .iter()
+        .find(|(t, _)| t2.duration_since(*t) <= VELOCITY_WINDOW)
+    else {
+        return Velocity::default();
+    };
+    let dt = t2.duration_since(*t1).as_secs_f32();
+    // Samples arriving in the same tick or batch say nothing about speed
+    if dt < 0.01 {
+        return Velocity::default();
+    }
+
+    let mut velocity = Velocity {
+        x: (p2.x - p1.x) / dt,
+        y: (p2.y - p1.y) / dt,
+        z: (p2.z - p1.z) / dt,
+    };
+    let speed = (velocity.x.powi(2) + velocity.y.powi(2) + velocity.z.powi(2)).sqrt();
+    if speed > MAX_ESTIMATED_SPEED {
+        let scale = MAX_ESTIMATED_SPEED / speed;
+        velocity.x *= scale;
+        velocity.y *= scale;
+        velocity.z *= scale;
+    }
+    velocity
+}
+
+// Where the player is expected to be `lookahead` seconds from now, based on their last reported Position and Velocity.
+// Use a lookahead of 0 for the player's current estimated position.
+pub fn get_extrapolated_position(player: &EntityView<'_>, lookahead: f32) -> Option<Position> {
+    let position = player.try_get::<&Position>(|p| *p)?;
+    let (Some(velocity), Some(last_update)) = (
+        player.try_get::<&Velocity>(|v| *v),
+        player.try_get::<&LastStatusUpdate>(|u| u.time),
+    ) else {
+        return Some(position);
+    };
+
+    let elapsed = Instant::now().duration_since(last_update).as_secs_f32() + lookahead.max(0.0);
+    let t = elapsed.min(MAX_EXTRAPOLATION_TIME.as_secs_f32());
+    Some(Position {
+        x: position.x + velocity.x * t,
+        y: position.y + velocity.y * t,
+        z: position.z + velocity.z * t,
+    })
+}
+
+// How long to wait before resolving a mechanic against get_position_at,
+// so that status updates sent around that time have arrived
+pub const RESOLVE_DELAY: Duration = Duration::from_millis(300);
+
+// Where the player was at `time`, interpolated between the reported positions around it.
+// Times outside of the kept history use the closest reported position.
+pub fn get_position_at(player: &EntityView<'_>, time: Instant) -> Option<Position> {
+    let position = player.try_get::<&Position>(|p| *p)?;
+    let interpolated = player.try_get::<&PositionHistory>(|history| {
+        let after = history.samples.iter().position(|(t, _)| *t >= time);
+        match after {
+            None => history.samples.back().map(|(_, p)| *p),
+            Some(0) => history.samples.front().map(|(_, p)| *p),
+            Some(i) => {
+                let (t1, p1) = history.samples[i - 1];
+                let (t2, p2) = history.samples[i];
+                let f = time.duration_since(t1).as_secs_f32() / t2.duration_since(t1).as_secs_f32();
+                Some(Position {
+                    x: p1.x + (p2.x - p1.x) * f,
+                    y: p1.y + (p2.y - p1.y) * f,
+                    z: p1.z + (p2.z - p1.z) * f,
+                })
+            }
+        }
+    });
+    Some(interpolated.flatten().unwrap_or(position))
+}
+
+// Records the player's MovementFlags, along with the time they changed
+pub fn record_movement_flags(player: &EntityView<'_>, flags: MovementFlags, time: Instant) {
+    if player
+        .try_get::<&MovementFlags>(|f| *f == flags)
+        .unwrap_or(false)
+    {
+        return;
+    }
+    player.set(flags);
+
+    if !player.has(MovementFlagsHistory::id()) {
+        player.set(MovementFlagsHistory::default());
+    }
+    player.get::<&mut MovementFlagsHistory>(|history| {
+        history.samples.push_back((time, flags));
+        // Always keep the newest change from before the window, since it's still in effect at the start of it
+        while history.samples.len() > MAX_MOVEMENT_FLAGS_SAMPLES
+            || history
+                .samples
+                .get(1)
+                .is_some_and(|(t, _)| time.duration_since(*t) > MOVEMENT_FLAGS_WINDOW)
+        {
+            history.samples.pop_front();
+        }
+    });
+}
+
+// Whether any of `flag` was set at some point between `from` and `to`
+pub fn had_movement_flag_between(
+    player: &EntityView<'_>,
+    flag: u8,
+    from: Instant,
+    to: Instant,
+) -> bool {
+    player
+        .try_get::<&MovementFlagsHistory>(|history| {
+            history
+                .samples
+                .iter()
+                .enumerate()
+                .any(|(i, (start, flags))| {
+                    // Each sample is in effect until the next one
+                    let end = history.samples.get(i + 1).map(|(t, _)| *t);
+                    flags.contains(flag) && *start <= to && end.is_none_or(|end| end > from)
+                })
+        })
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn position(x: f32) -> Position {
+        Position { x, y: 0.0, z: 0.0 }
+    }
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    // Records positions the way UpdateStatus does, returning the time of the first sample
+    fn create_player<'a>(world: &'a World, samples: &[(u64, f32)]) -> (EntityView<'a>, Instant) {
+        let player = world.entity();
+        let start = Instant::now();
+        for (millis, x) in samples {
+            let time = start + ms(*millis);
+            player.set(position(*x)).set(LastStatusUpdate { time });
+            record_position_sample(&player, position(*x), time);
+        }
+        (player, start)
+    }
+
+    fn x_at(player: &EntityView<'_>, time: Instant) -> f32 {
+        get_position_at(player, time).unwrap().x
+    }
+
+    #[test]
+    fn position_is_interpolated_between_samples() {
+        let world = World::new();
+        let (player, start) = create_player(&world, &[(0, 0.0), (100, 10.0), (300, 10.0)]);
+
+        assert!((x_at(&player, start + ms(25)) - 2.5).abs() < 0.01);
+        assert!((x_at(&player, start + ms(100)) - 10.0).abs() < 0.01);
+        assert!((x_at(&player, start + ms(200)) - 10.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn position_is_clamped_outside_of_history() {
+        let world = World::new();
+        let (player, start) = create_player(&world, &[(100, 1.0), (200, 2.0)]);
+
+        assert_eq!(x_at(&player, start), 1.0);
+        assert_eq!(x_at(&player, start + ms(1000)), 2.0);
+    }
+
+    #[test]
+    fn position_without_history_is_the_current_position() {
+        let world = World::new();
+        let player = world.entity().set(position(5.0));
+
+        assert_eq!(x_at(&player, Instant::now()), 5.0);
+        assert!(get_position_at(&world.entity(), Instant::now()).is_none());
+    }
+
+    #[test]
+    fn old_samples_are_evicted() {
+        let world = World::new();
+        let window = POSITION_HISTORY_WINDOW.as_millis() as u64;
+        let (player, start) = create_player(
+            &world,
+            &[
+                (0, 0.0),
+                (100, 1.0),
+                (window + 50, 2.0),
+                (window + 100, 3.0),
+            ],
+        );
+
+        player.get::<&PositionHistory>(|history| {
+            assert_eq!(history.samples.len(), 3);
+            assert_eq!(history.samples.front().unwrap().1.x, 1.0);
+        });
+        // The evicted sample can no longer be interpolated towards
+        assert_eq!(x_at(&player, start), 1.0);
+
+        let samples: Vec<(u64, f32)> = (0..MAX_POSITION_SAMPLES as u64 + 10)
+            .map(|i| (i, i as f32))
+            .collect();
+        let (player, _) = create_player(&world, &samples);
+        player.get::<&PositionHistory>(|history| {
+            assert_eq!(history.samples.len(), MAX_POSITION_SAMPLES);
+            assert_eq!(history.samples.front().unwrap().1.x, 10.0);
+        });
+    }
+
+    #[test]
+    fn velocity_is_estimated_and_capped() {
+        let world = World::new();
+        let (player, _) = create_player(&world, &[(0, 0.0), (100, 1.0)]);
+        player.get::<&Velocity>(|v| assert!((v.x - 10.0).abs() < 0.01));
+
+        let (player, _) = create_player(&world, &[(0, 0.0), (100, 100.0)]);
+        player.get::<&Velocity>(|v| assert!((v.x - MAX_ESTIMATED_SPEED).abs() < 0.01));
+    }
+
+    #[test]
+    fn extrapolation_is_limited() {
+        let world = World::new();
+        let player = world
+            .entity()
+            .set(position(0.0))
+            .set(Velocity {
+                x: 10.0,
+                y: 0.0,
+                z: 0.0,
+            })
+            .set(LastStatusUpdate {
+                time: Instant::now(),
+            });
+
+        let x = get_extrapolated_position(&player, 0.1).unwrap().x;
+        assert!((0.9..1.1).contains(&x));
+        let x = get_extrapolated_position(&player, 10.0).unwrap().x;
+        assert!((x - 10.0 * MAX_EXTRAPOLATION_TIME.as_secs_f32()).abs() < 0.01);
+    }
+
+    #[test]
+    fn movement_flags_are_checked_over_a_time_range() {
+        let world = World::new();
+        let player = world.entity();
+        let start = Instant::now();
+        record_movement_flags(&player, MovementFlags(0), start);
+        record_movement_flags(
+            &player,
+            MovementFlags(MovementFlags::JUMPING),
+            start + ms(100),
+        );
+        record_movement_flags(&player, MovementFlags(0), start + ms(200));
+
+        let jumped = |from: u64, to: u64| {
+            had_movement_flag_between(
+                &player,
+                MovementFlags::JUMPING,
+                start + ms(from),
+                start + ms(to),
+            )
+        };
+        assert!(!jumped(0, 50));
+        assert!(jumped(50, 150));
+        assert!(jumped(150, 160));
+        assert!(!jumped(200, 300));
+        assert!(!had_movement_flag_between(
+            &player,
+            MovementFlags::MOUNTED,
+            start,
+            start + ms(300)
+        ));
+    }
+}
@@ -0,0 +1,347 @@
+use crate::{
+    game::{components::*, utils::*},
+    webserver::{message::RunMechanicCommandPayload, network_mechanic::NetworkMechanicCommand},
+};
+use flecs_ecs::prelude::*;
+use std::collections::{HashMap, VecDeque};
+use uuid::Uuid;
+
+// Projectiles are moving hitboxes simulated on the server, such as rolling balls. They're parented to the mechanic
+// that spawned them, so they're cleaned up along with it. Clients are told to spawn, move and despawn a visual
+// for each one, and only ever show it; hits are all decided here.
+
+// How often clients are sent a projectile's position while its velocity isn't changing direction
+const SYNC_INTERVAL: f32 = 0.25;
+// Velocity changes sharper than this (as the cosine of the angle between them) are synced right away
+const SYNC_DIRECTION_THRESHOLD: f32 = 0.995;
+
+// Called for each player the projectile hits. The projectile's Position is passed in separately,
+// since the projectile system is still writing to it.
+pub type ProjectileHitFn =
+    fn(projectile: &EntityView<'_>, projectile_position: &Position, player: &EntityView<'_>);
+
+#[derive(Component, Debug)]
+pub struct Projectile {
+    // Settings
+    pub id: u128,
+    // Tells clients what to show, such as a model id, interpreted by the spawning mechanic's client code
+    pub visual: String,
+    pub radius: f32,
+    // How long before the same player can be hit again
+    pub hit_cooldown: f32,
+    // Players aren't hit until this runs out, e.g. while the visual is still appearing
+    pub time_until_active: f32,
+    pub on_hit: ProjectileHitFn,
+    // Runtime
+    hit_cooldowns: HashMap<Entity, f32>,
+    time_to_next_sync: f32,
+    synced_velocity: Velocity,
+}
+
+impl Projectile {
+    pub fn new(visual: String, radius: f32, hit_cooldown: f32, on_hit: ProjectileHitFn) -> Self {
+        Self {
+            id: Uuid::new_v4().as_u128(),
+            visual,
+            radius,
+            hit_cooldown,
+            time_until_active: 0.0,
+            on_hit,
+            hit_cooldowns: HashMap::new(),
+            time_to_next_sync: SYNC_INTERVAL,
+            synced_velocity: Velocity::default(),
+        }
+    }
+}
+
+// Waypoints the projectile moves through in order. Its Velocity is steered towards the next one,
+// and set to zero once the last one is reached.
+#[derive(Component, Debug)]
+pub struct ProjectilePath {
+    pub waypoints: VecDeque<Position>,
+    pub speed: f32,
+}
+
+pub fn spawn_projectile(
+    mechanic: &EntityView<'_>,
+    party_id: &str,
+    projectile: Projectile,
+    position: Position,
+    velocity: Velocity,
+) -> Entity {
+    let world = mechanic.world();
+    broadcast_run_mechanic_command(
+        &world,
+        party_id,
+        RunMechanicCommandPayload {
+            mechanic_command_id: NetworkMechanicCommand::SpawnProjectile as i32,
+            world_position_x: Some(position.x),
+            world_position_y: Some(position.y),
+            world_position_z: Some(position.z),
+            rotation: Some(vector_to_rotation(velocity.x, velocity.z)),
+            extra_data: Some(format!("{},{}", projectile.id, projectile.visual)),
+        },
+    );
+
+    *world
+        .entity()
+        .child_of(*mechanic)
+        .set(Projectile {
+            synced_velocity: velocity,
+            ..projectile
+        })
+        .set(position)
+        .set(velocity)
+}
+
+pub fn create_systems(world: &World) {
+    // Follow paths
+    world
+        .system::<(&mut ProjectilePath, &Position, &mut Velocity)>()
+        .with(Projectile::id())
+        .each_iter(|it, index, (path, position, velocity)| {
+            let dt = it.delta_time();
+            let step = path.speed * dt;
+            while let Some(waypoint) = path.waypoints.front().copied() {
+                let (dx, dy, dz) = (
+                    waypoint.x - position.x,
+                    waypoint.y - position.y,
+                    waypoint.z - position.z,
+                );
+                let distance = (dx.powi(2) + dy.powi(2) + dz.powi(2)).sqrt();
+                if distance > step {
+                    *velocity = Velocity {
+                        x: dx / distance * path.speed,
+                        y: dy / distance * path.speed,
+                        z: dz / distance * path.speed,
+                    };
+                    return;
+                }
+                path.waypoints.pop_front();
+                // Land exactly on the last waypoint this tick, and stop on the next one
+                if path.waypoints.is_empty() && dt > 0.0 {
+                    *velocity = Velocity {
+                        x: dx / dt,
+                        y: dy / dt,
+                        z: dz / dt,
+                    };
+                    return;
+                }
+            }
+            *velocity = Velocity::default();
+            it.entity(index).remove(ProjectilePath::id());
+        });
+
+    // Move and hit players
+    world
+        .system::<(&mut Projectile, &mut Position, &Velocity, &Party)>()
+        .term_at(3)
+        .up()
+        .each_iter(|it, index, (projectile, position, velocity, party)| {
+            let entity = it.entity(index);
+            let world = &it.world();
+            let dt = it.delta_time();
+
+            let start = *position;
+            position.x += velocity.x * dt;
+            position.y += velocity.y * dt;
+            position.z += velocity.z * dt;
+
+            // Sync to clients, unless it's sitting still
+            let is_moving = velocity.x != 0.0 || velocity.y != 0.0 || velocity.z != 0.0;
+            projectile.time_to_next_sync -= dt;
+            if (is_moving && projectile.time_to_next_sync <= 0.0)
+                || has_changed_direction(&projectile.synced_velocity, velocity)
+            {
+                projectile.time_to_next_sync = SYNC_INTERVAL;
+                projectile.synced_velocity = *velocity;
+                broadcast_run_mechanic_command(
+                    world,
+                    &party.id,
+                    RunMechanicCommandPayload {
+                        mechanic_command_id: NetworkMechanicCommand::MoveProjectile as i32,
+                        world_position_x: Some(position.x),
+                        world_position_y: Some(position.y),
+                        world_position_z: Some(position.z),
+                        rotation: Some(vector_to_rotation(velocity.x, velocity.z)),
+                        extra_data: Some(format!(
+                            "{},{},{},{}",
+                            projectile.id, velocity.x, velocity.y, velocity.z
+                        )),
+                    },
+                );
+            }
+
+            projectile.hit_cooldowns.retain(|_, cooldown| {
+                *cooldown -= dt;
+                *cooldown > 0.0
+            });
+
+            if projectile.time_until_active > 0.0 {
+                projectile.time_until_active = f32::max(projectile.time_until_active - dt, 0.0);
+                return;
+            }
+
+            // Test against the whole distance moved this tick, so fast projectiles don't skip over players
+            let extent = entity.try_get::<&VerticalExtent>(|v| *v);
+            let mut hits: Vec<Entity> = Vec::new();
+            if let Some(pc) = find_party_container(world, &party.id) {
+                pc.each_child(|c| {
+                    if c.has(Stale::id()) || projectile.hit_cooldowns.contains_key(&c) {
+                        return;
+                    }
+                    c.try_get::<(&Player, &Position, &State)>(|(_, p, s)| {
+                        if s.is_alive
+                            && is_in_swept_circle(
+                                &start,
+                                position,
+                                projectile.radius,
+                                extent.as_ref(),
+                                p,
+                            )
+                        {
+                            hits.push(*c);
+                        }
+                    });
+                });
+            }
+
+            for player in hits {
+                projectile
+                    .hit_cooldowns
+                    .insert(player, projectile.hit_cooldown);
+                (projectile.on_hit)(&entity, position, &player.entity_view(world));
+            }
+        });
+}
+
+fn has_changed_direction(a: &Velocity, b: &Velocity) -> bool {
+    let length_a = (a.x.powi(2) + a.y.powi(2) + a.z.powi(2)).sqrt();
+    let length_b = (b.x.powi(2) + b.y.powi(2) + b.z.powi(2)).sqrt();
+    if length_a == 0.0 || length_b == 0.0 {
+        return length_a != length_b;
+    }
+    (a.x * b.x + a.y * b.y + a.z * b.z) / (length_a * length_b) < SYNC_DIRECTION_THRESHOLD
+}
+
+pub fn create_observers(world: &World) {
+    // Tell clients to remove the visual
+    world
+        .observer::<flecs::OnRemove, (&Projectile, &Party)>()
+        .term_at(1)
+        .up()
+        .each_iter(|it, _index, (projectile, party)| {
+            broadcast_run_mechanic_command(
+                &it.world(),
+                &party.id,
+                RunMechanicCommandPayload {
+                    mechanic_command_id: NetworkMechanicCommand::DespawnProjectile as i32,
+                    extra_data: Some(projectile.id.to_string()),
+                    ..Default::default()
+                },
+            );
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Component)]
+    struct Hit;
+
+    fn on_hit(_: &EntityView<'_>, _: &Position, player: &EntityView<'_>) {
+        player.add(Hit);
+    }
+
+    fn position(x: f32, z: f32) -> Position {
+        Position { x, y: 0.0, z }
+    }
+
+    #[test]
+    fn projectile_follows_its_path_and_hits_players_along_it() {
+        let world = World::new();
+        world.set(OutboundMessageQueue::default());
+        world.set(PartyIndex::default());
+        let pc = world.entity().add(PartyContainer).set(Party {
+            id: "p".to_string(),
+        });
+        world.get::<&mut PartyIndex>(|index| {
+            index.party_containers.insert("p".to_string(), *pc);
+        });
+        let player = world
+            .entity()
+            .child_of(pc)
+            .set(Player {
+                content_id: 0,
+                name: "Player".to_string(),
+            })
+            .set(State { is_alive: true })
+            .set(position(1.0, 2.0));
+        let mechanic = world.entity().child_of(pc);
+        create_systems(&world);
+
+        let projectile = spawn_projectile(
+            &mechanic,
+            "p",
+            Projectile::new(String::new(), 0.5, 10.0, on_hit),
+            position(0.0, 0.0),
+            Velocity::default(),
+        )
+        .entity_view(&world);
+        projectile.set(ProjectilePath {
+            waypoints: VecDeque::from([position(0.0, 2.0), position(2.0, 2.0)]),
+            speed: 3.0,
+        });
+
+        for _ in 0..30 {
+            world.progress_time(0.1);
+        }
+
+        assert!(!projectile.has(ProjectilePath::id()));
+        projectile.get::<(&Position, &Velocity)>(|(p, v)| {
+            assert!((p.x - 2.0).abs() < 1e-4 && (p.z - 2.0).abs() < 1e-4);
+            assert_eq!((v.x, v.y, v.z), (0.0, 0.0, 0.0));
+        });
+        assert!(player.has(Hit));
+    }
+
+    #[test]
+    fn projectile_path_turns_at_each_waypoint() {
+        let world = World::new();
+        world.set(OutboundMessageQueue::default());
+        world.set(PartyIndex::default());
+        let pc = world.entity().add(PartyContainer).set(Party {
+            id: "p".to_string(),
+        });
+        let mechanic = world.entity().child_of(pc);
+        create_systems(&world);
+
+        let projectile = spawn_projectile(
+            &mechanic,
+            "p",
+            Projectile::new(String::new(), 0.5, 10.0, on_hit),
+            position(0.0, 0.0),
+            Velocity::default(),
+        )
+        .entity_view(&world);
+        projectile.set(ProjectilePath {
+            waypoints: VecDeque::from([position(0.0, 2.0), position(2.0, 2.0)]),
+            speed: 4.0,
+        });
+
+        // Heading for the first waypoint
+        world.progress_time(0.1);
+        projectile.get::<&Velocity>(|v| assert_eq!((v.x, v.z), (0.0, 4.0)));
+
+        // Within a step of it, and heading for the second
+        for _ in 0..5 {
+            world.progress_time(0.1);
+        }
+        projectile.get::<&ProjectilePath>(|path| assert_eq!(path.waypoints.len(), 1));
+        projectile.get::<&Velocity>(|v| {
+            assert!(v.x > 0.0);
+            assert!(((v.x.powi(2) + v.z.powi(2)).sqrt() - 4.0).abs() < 1e-4);
+        });
+    }
+}
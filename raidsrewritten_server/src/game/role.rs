@@ -1,7 +1,17 @@
 use serde_repr::*;
-use strum_macros::IntoStaticStr;
+use strum_macros::{EnumIter, IntoStaticStr};
 
-#[derive(Serialize_repr, Deserialize_repr, PartialEq, IntoStaticStr, Default, Debug)]
+#[derive(
+    Serialize_repr,
+    Deserialize_repr,
+    PartialEq,
+    IntoStaticStr,
+    EnumIter,
+    Default,
+    Copy,
+    Clone,
+    Debug,
+)]
 #[repr(u32)]
 pub enum Role {
     #[default]
@@ -0,0 +1,25 @@
+use crate::game::components::*;
+use flecs_ecs::prelude::*;
+use std::time::{Duration, Instant};
+use tracing::info;
+
+// A player who hasn't sent an UpdateStatus within this time (alt-tabbed, zoning, crashed plugin)
+// is marked Stale, and mechanics stop treating their last Position as where they are.
+pub const STALE_STATUS_THRESHOLD: Duration = Duration::from_secs(3);
+
+pub fn create_systems(world: &World) {
+    world
+        .system::<(&Player, &LastStatusUpdate)>()
+        .without(Stale)
+        .each_iter(|it, i, (player, last_status_update)| {
+            let elapsed = Instant::now().duration_since(last_status_update.time);
+            if elapsed > STALE_STATUS_THRESHOLD {
+                info!(
+                    player.name,
+                    elapsed_ms = elapsed.as_millis(),
+                    "Player status is stale"
+                );
+                it.entity(i).add(Stale);
+            }
+        });
+}
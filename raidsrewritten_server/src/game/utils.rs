@@ -1,8 +1,15 @@
-use crate::{game::components::*, webserver::message::*};
+use crate::{
+    game::components::*,
+    webserver::{handshake::ClientCapabilities, message::*, metrics::*},
+};
+use distances::vectors::euclidean_sq;
 use flecs_ecs::prelude::*;
-use socketioxide::{SocketIo, socket::Sid};
-use std::collections::HashMap;
-use tracing::info;
+use socketioxide::{SocketIo, extract::SocketRef, socket::Sid};
+use std::{
+    collections::HashMap,
+    f32::consts::{PI, TAU},
+};
+use tracing::{error, info};
 
 // Math Utils
 
@@ -17,6 +24,158 @@ pub fn get_angle_between_lines(a1: [f32; 2], a2: [f32; 2], b1: [f32; 2], b2: [f3
     ((cos_val * 10000.0).round() / 10000.0).acos()
 }
 
+// Whether something at `position`, facing `rotation`, is facing `target` within `max_angle_degrees` to either side.
+// Rotations follow vector_to_rotation, so 0 faces +z.
+pub fn is_facing(
+    position: &Position,
+    rotation: f32,
+    target: &Position,
+    max_angle_degrees: f32,
+) -> bool {
+    let dx = target.x - position.x;
+    let dz = target.z - position.z;
+    if dx == 0.0 && dz == 0.0 {
+        return true;
+    }
+    let angle_to_target = vector_to_rotation(dx, dz);
+    let difference = (angle_to_target - rotation + PI).rem_euclid(TAU) - PI;
+    difference.abs() <= max_angle_degrees.to_radians()
+}
+
+// Whether a player or other actor with a Position and Rotation is facing `target`
+pub fn is_entity_facing(
+    entity: &EntityView<'_>,
+    target: &Position,
+    max_angle_degrees: f32,
+) -> bool {
+    entity
+        .try_get::<(&Position, &Rotation)>(|(p, r)| {
+            is_facing(p, r.value, target, max_angle_degrees)
+        })
+        .unwrap_or(false)
+}
+
+// Hit Testing
+
+pub fn is_within_vertical_extent(origin_y: f32, extent: Option<&VerticalExtent>, y: f32) -> bool {
+    match extent {
+        Some(extent) => y >= origin_y - extent.below && y <= origin_y + extent.above,
+        None => true,
+    }
+}
+
+pub fn is_in_circle(
+    center: &Position,
+    radius: f32,
+    extent: Option<&VerticalExtent>,
+    point: &Position,
+) -> bool {
+    let distance_sq: f32 = euclidean_sq(&[center.x, center.z], &[point.x, point.z]);
+    distance_sq <= radius.powi(2) && is_within_vertical_extent(center.y, extent, point.y)
+}
+
+pub fn is_in_donut(
+    center: &Position,
+    inner_radius: f32,
+    outer_radius: f32,
+    extent: Option<&VerticalExtent>,
+    point: &Position,
+) -> bool {
+    let distance_sq: f32 = euclidean_sq(&[center.x, center.z], &[point.x, point.z]);
+    distance_sq >= inner_radius.powi(2)
+        && distance_sq <= outer_radius.powi(2)
+        && is_within_vertical_extent(center.y, extent, point.y)
+}
+
+// Whether a circle moving from `start` to `end` passed over `point` at some point along the way,
+// so fast moving hitboxes can't skip over players between ticks
+pub fn is_in_swept_circle(
+    start: &Position,
+    end: &Position,
+    radius: f32,
+    extent: Option<&VerticalExtent>,
+    point: &Position,
+) -> bool {
+    let (dx, dz) = (end.x - start.x, end.z - start.z);
+    let length_sq = dx.powi(2) + dz.powi(2);
+    let t = if length_sq > 0.0 {
+        (((point.x - start.x) * dx + (point.z - start.z) * dz) / length_sq).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    let closest = Position {
+        x: start.x + dx * t,
+        y: start.y + (end.y - start.y) * t,
+        z: start.z + dz * t,
+    };
+    is_in_circle(&closest, radius, extent, point)
+}
+
+// How far `angle` is from the arc swept from `start_rotation` to `end_rotation`, which is 0 if it's on the arc.
+// The arc may go either way, and wraps around fully if it covers a whole turn.
+fn angle_to_arc(angle: f32, start_rotation: f32, end_rotation: f32) -> f32 {
+    let (from, length) = if end_rotation >= start_rotation {
+        (start_rotation, end_rotation - start_rotation)
+    } else {
+        (end_rotation, start_rotation - end_rotation)
+    };
+    if length >= TAU {
+        return 0.0;
+    }
+    let relative = (angle - from).rem_euclid(TAU);
+    if relative <= length {
+        0.0
+    } else {
+        f32::min(relative - length, TAU - relative)
+    }
+}
+
+// Whether a cone rotating about `center` from `start_rotation` to `end_rotation` passed over `point`,
+// where `half_angle_degrees` is the angle to either side of the cone's facing
+pub fn is_in_swept_cone(
+    center: &Position,
+    radius: f32,
+    half_angle_degrees: f32,
+    start_rotation: f32,
+    end_rotation: f32,
+    extent: Option<&VerticalExtent>,
+    point: &Position,
+) -> bool {
+    let (dx, dz) = (point.x - center.x, point.z - center.z);
+    if !is_in_circle(center, radius, extent, point) {
+        return false;
+    }
+    if dx == 0.0 && dz == 0.0 {
+        return true;
+    }
+    angle_to_arc(vector_to_rotation(dx, dz), start_rotation, end_rotation)
+        <= half_angle_degrees.to_radians()
+}
+
+// Whether a line of `length` and `half_width` to either side, extending from `center` and rotating about it
+// from `start_rotation` to `end_rotation`, passed over `point`
+pub fn is_in_swept_line(
+    center: &Position,
+    length: f32,
+    half_width: f32,
+    start_rotation: f32,
+    end_rotation: f32,
+    extent: Option<&VerticalExtent>,
+    point: &Position,
+) -> bool {
+    let (dx, dz) = (point.x - center.x, point.z - center.z);
+    if !is_within_vertical_extent(center.y, extent, point.y) {
+        return false;
+    }
+    let distance = (dx.powi(2) + dz.powi(2)).sqrt();
+    if distance <= half_width {
+        return true;
+    }
+    let offset = angle_to_arc(vector_to_rotation(dx, dz), start_rotation, end_rotation);
+    let (along, across) = (distance * offset.cos(), distance * offset.sin());
+    (0.0..=length).contains(&along) && across <= half_width
+}
+
 // Other Utils
 
 pub fn convert_to_transform(
@@ -41,6 +200,19 @@ pub fn convert_to_transform(
     }
 }
 
+pub fn convert_to_vertical_extent(
+    below: Option<f32>,
+    above: Option<f32>,
+) -> Option<VerticalExtent> {
+    if let Some(below) = below
+        && let Some(above) = above
+    {
+        Some(VerticalExtent { below, above })
+    } else {
+        None
+    }
+}
+
 pub fn get_entity_view<'a>(entity: &Entity, world: &WorldRef<'a>) -> Option<EntityView<'a>> {
     let ev = entity.entity_view(world);
     if ev.is_valid() { Some(ev) } else { None }
@@ -60,16 +232,27 @@ pub fn get_target_ids(entity: &EntityView<'_>) -> Vec<u64> {
     targets
 }
 
-pub fn get_socket_io(world: &WorldRef<'_>) -> SocketIo {
-    world.get::<&SocketIoSingleton>(|sio| sio.io.clone())
+pub fn get_socket_io(world: &WorldRef<'_>) -> Option<SocketIo> {
+    let io = world.try_get::<&SocketIoSingleton>(|sio| sio.io.clone());
+    if io.is_none() {
+        error!("SocketIoSingleton is missing from the world");
+    }
+    io
+}
+
+pub fn find_party_container<'a>(world: &WorldRef<'a>, party: &str) -> Option<EntityView<'a>> {
+    let entity = world.get::<&PartyIndex>(|index| index.party_containers.get(party).copied())?;
+    get_entity_view(&entity, world)
 }
 
-pub fn find_party_container<'a>(world: &World, party: &String) -> Option<EntityView<'a>> {
-    world
-        .query::<&Party>()
-        .with(&PartyContainer)
-        .build()
-        .find(|p| p.id == *party)
+pub fn find_player<'a>(world: &WorldRef<'a>, content_id: u64) -> Option<EntityView<'a>> {
+    let entity = world.get::<&PartyIndex>(|index| index.players.get(&content_id).copied())?;
+    get_entity_view(&entity, world)
+}
+
+pub fn find_socket<'a>(world: &WorldRef<'a>, socket_id: Sid) -> Option<EntityView<'a>> {
+    let entity = world.get::<&PartyIndex>(|index| index.sockets.get(&socket_id).copied())?;
+    get_entity_view(&entity, world)
 }
 
 pub fn add_affect(affects: &mut HashMap<Entity, u8>, entity: &Entity, affect_count: u8) {
@@ -81,13 +264,13 @@ pub fn add_affect(affects: &mut HashMap<Entity, u8>, entity: &Entity, affect_cou
 }
 
 pub fn send_apply_condition(
-    io: SocketIo,
+    world: &WorldRef<'_>,
     socket_id: Sid,
     condition_payload: ApplyConditionPayload,
 ) {
     info!(socket_str = socket_id.as_str(), "Sending apply_condition");
     send_message(
-        io,
+        world,
         socket_id,
         Message {
             action: Action::ApplyCondition,
@@ -97,14 +280,18 @@ pub fn send_apply_condition(
     );
 }
 
-pub fn send_play_static_vfx(io: SocketIo, socket_id: Sid, payload: PlayStaticVfxPayload) {
+pub fn broadcast_play_static_vfx(
+    world: &WorldRef<'_>,
+    party_id: &str,
+    payload: PlayStaticVfxPayload,
+) {
     info!(
-        socket_str = socket_id.as_str(),
-        payload.id, payload.vfx_path, "Sending play_static_vfx"
+        party_id,
+        payload.id, payload.vfx_path, "Broadcasting play_static_vfx"
     );
-    send_message(
-        io,
-        socket_id,
+    broadcast_message(
+        world,
+        party_id,
         Message {
             action: Action::PlayStaticVfx,
             play_static_vfx: Some(payload),
@@ -113,18 +300,18 @@ pub fn send_play_static_vfx(io: SocketIo, socket_id: Sid, payload: PlayStaticVfx
     );
 }
 
-pub fn send_play_actor_vfx_on_target(
-    io: SocketIo,
-    socket_id: Sid,
+pub fn broadcast_play_actor_vfx_on_target(
+    world: &WorldRef<'_>,
+    party_id: &str,
     payload: PlayActorVfxOnTargetPayload,
 ) {
     info!(
-        socket_str = socket_id.as_str(),
-        payload.vfx_path, "Sending play_actor_vfx_on_target"
+        party_id,
+        payload.vfx_path, "Broadcasting play_actor_vfx_on_target"
     );
-    send_message(
-        io,
-        socket_id,
+    broadcast_message(
+        world,
+        party_id,
         Message {
             action: Action::PlayActorVfxOnTarget,
             play_actor_vfx_on_target: Some(payload),
@@ -133,18 +320,18 @@ pub fn send_play_actor_vfx_on_target(
     );
 }
 
-pub fn send_play_actor_vfx_on_position(
-    io: SocketIo,
-    socket_id: Sid,
+pub fn broadcast_play_actor_vfx_on_position(
+    world: &WorldRef<'_>,
+    party_id: &str,
     payload: PlayActorVfxOnPositionPayload,
 ) {
     info!(
-        socket_str = socket_id.as_str(),
-        payload.vfx_path, "Sending play_actor_vfx_on_position"
+        party_id,
+        payload.vfx_path, "Broadcasting play_actor_vfx_on_position"
     );
-    send_message(
-        io,
-        socket_id,
+    broadcast_message(
+        world,
+        party_id,
         Message {
             action: Action::PlayActorVfxOnPosition,
             play_actor_vfx_on_position: Some(payload),
@@ -153,14 +340,31 @@ pub fn send_play_actor_vfx_on_position(
     );
 }
 
-pub fn send_stop_vfx(io: SocketIo, socket_id: Sid, payload: StopVfxPayload) {
+pub fn broadcast_play_tether_vfx(
+    world: &WorldRef<'_>,
+    party_id: &str,
+    payload: PlayTetherVfxPayload,
+) {
     info!(
-        socket_str = socket_id.as_str(),
-        payload.id, "Sending stop_vfx"
+        party_id,
+        payload.id, payload.vfx_path, "Broadcasting play_tether_vfx"
     );
-    send_message(
-        io,
-        socket_id,
+    broadcast_message(
+        world,
+        party_id,
+        Message {
+            action: Action::PlayTetherVfx,
+            play_tether_vfx: Some(payload),
+            ..Default::default()
+        },
+    );
+}
+
+pub fn broadcast_stop_vfx(world: &WorldRef<'_>, party_id: &str, payload: StopVfxPayload) {
+    info!(party_id, payload.id, "Broadcasting stop_vfx");
+    broadcast_message(
+        world,
+        party_id,
         Message {
             action: Action::StopVfx,
             stop_vfx: Some(payload),
@@ -169,14 +373,18 @@ pub fn send_stop_vfx(io: SocketIo, socket_id: Sid, payload: StopVfxPayload) {
     );
 }
 
-pub fn send_run_mechanic_command(io: SocketIo, socket_id: Sid, payload: RunMechanicCommandPayload) {
+pub fn broadcast_run_mechanic_command(
+    world: &WorldRef<'_>,
+    party_id: &str,
+    payload: RunMechanicCommandPayload,
+) {
     info!(
-        socket_str = socket_id.as_str(),
-        payload.mechanic_command_id, "Sending run_mechanic_command"
+        party_id,
+        payload.mechanic_command_id, "Broadcasting run_mechanic_command"
     );
-    send_message(
-        io,
-        socket_id,
+    broadcast_message(
+        world,
+        party_id,
         Message {
             action: Action::RunMechanicCommand,
             run_mechanic_command: Some(payload),
@@ -185,8 +393,260 @@ pub fn send_run_mechanic_command(io: SocketIo, socket_id: Sid, payload: RunMecha
     );
 }
 
-pub fn send_message(io: SocketIo, socket_id: Sid, message: Message) {
-    tokio::spawn(async move {
-        io.to(socket_id).emit("message", &message).await.unwrap();
+pub fn send_message(world: &WorldRef<'_>, socket_id: Sid, message: Message) {
+    world.get::<&mut OutboundMessageQueue>(|queue| {
+        queue
+            .messages
+            .push((OutboundTarget::Socket(socket_id), message));
+    });
+}
+
+// Sends a message to everyone in the party's room, rather than the caller looking up each party member
+pub fn broadcast_message(world: &WorldRef<'_>, party_id: &str, message: Message) {
+    world.get::<&mut OutboundMessageQueue>(|queue| {
+        queue
+            .messages
+            .push((OutboundTarget::Party(party_id.to_string()), message));
     });
 }
+
+pub fn party_room(party_id: &str) -> String {
+    format!("party:{party_id}")
+}
+
+// Sends all messages queued during the tick to each socket in the order they were queued.
+// Party messages are expanded to every socket in the party's room, and anything a socket can't handle is left out.
+// Sockets whose handshake listed Batch get them as a single message, and every other socket gets them one by one.
+pub fn flush_outbound_messages(world: &World) {
+    let queued =
+        world.get::<&mut OutboundMessageQueue>(|queue| std::mem::take(&mut queue.messages));
+    if queued.is_empty() {
+        return;
+    }
+
+    let Some(io) = get_socket_io(&world.world()) else {
+        OUTBOUND_MESSAGES_FAILED
+            .with_label_values(&["no_socket_io"])
+            .inc_by(queued.len() as u64);
+        return;
+    };
+
+    let mut rooms: HashMap<String, Vec<SocketRef>> = HashMap::new();
+    let mut sockets: HashMap<Sid, (SocketRef, Vec<Message>)> = HashMap::new();
+    for (target, message) in queued {
+        match target {
+            OutboundTarget::Socket(socket_id) => {
+                if let Some((_, messages)) = sockets.get_mut(&socket_id) {
+                    messages.push(message);
+                } else if let Some(socket) = io.get_socket(socket_id) {
+                    sockets.insert(socket_id, (socket, vec![message]));
+                }
+            }
+            OutboundTarget::Party(party_id) => {
+                let members = rooms
+                    .entry(party_id)
+                    .or_insert_with_key(|party_id| io.to(party_room(party_id)).sockets());
+                for socket in members.iter() {
+                    sockets
+                        .entry(socket.id)
+                        .or_insert_with(|| (socket.clone(), Vec::new()))
+                        .1
+                        .push(message.clone());
+                }
+            }
+        }
+    }
+
+    let legacy = ClientCapabilities::legacy();
+    world.get::<&ClientHandshakes>(|handshakes| {
+        for (socket_id, (socket, messages)) in sockets {
+            let capabilities = handshakes.sockets.get(&socket_id).unwrap_or(&legacy);
+            let messages: Vec<Message> = messages
+                .into_iter()
+                .filter_map(|m| capabilities.filter_message(m))
+                .collect();
+            let messages = if messages.is_empty() {
+                messages
+            } else if capabilities.supports_action(Action::Batch) {
+                vec![batch_messages(messages)]
+            } else {
+                messages
+            };
+            for message in messages {
+                if let Err(e) = socket.emit("message", &message) {
+                    error!(socket_str = socket_id.as_str(), error = %e, "Failed to send message");
+                    OUTBOUND_MESSAGES_FAILED.with_label_values(&["emit"]).inc();
+                }
+            }
+        }
+    });
+}
+
+fn batch_messages(mut messages: Vec<Message>) -> Message {
+    if messages.len() == 1 {
+        messages.remove(0)
+    } else {
+        Message {
+            action: Action::Batch,
+            batch: Some(BatchPayload { messages }),
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::{FRAC_PI_2, FRAC_PI_4};
+
+    const ORIGIN: Position = Position {
+        x: 0.0,
+        y: 0.0,
+        z: 0.0,
+    };
+
+    // The point `distance` away from the origin in the direction of `rotation`, following vector_to_rotation
+    fn point_at(rotation: f32, distance: f32) -> Position {
+        Position {
+            x: rotation.sin() * distance,
+            y: 0.0,
+            z: rotation.cos() * distance,
+        }
+    }
+
+    fn cone(start_rotation: f32, end_rotation: f32, point: &Position) -> bool {
+        is_in_swept_cone(
+            &ORIGIN,
+            10.0,
+            5.0,
+            start_rotation,
+            end_rotation,
+            None,
+            point,
+        )
+    }
+
+    fn line(start_rotation: f32, end_rotation: f32, point: &Position) -> bool {
+        is_in_swept_line(
+            &ORIGIN,
+            10.0,
+            1.0,
+            start_rotation,
+            end_rotation,
+            None,
+            point,
+        )
+    }
+
+    #[test]
+    fn swept_circle_hits_along_the_whole_path() {
+        let start = ORIGIN;
+        let end = Position { x: 10.0, ..ORIGIN };
+        let hit = |x: f32, z: f32| {
+            is_in_swept_circle(&start, &end, 1.0, None, &Position { x, y: 0.0, z })
+        };
+
+        // Passed over entirely within the one tick
+        assert!(hit(5.0, 0.5));
+        assert!(hit(10.5, 0.0));
+        assert!(hit(-0.5, 0.0));
+        assert!(!hit(5.0, 2.0));
+        assert!(!hit(11.5, 0.0));
+        assert!(is_in_swept_circle(
+            &start,
+            &start,
+            1.0,
+            None,
+            &point_at(0.0, 0.5)
+        ));
+    }
+
+    #[test]
+    fn swept_circle_respects_vertical_extent() {
+        let end = Position { x: 10.0, ..ORIGIN };
+        let extent = VerticalExtent {
+            below: 1.0,
+            above: 1.0,
+        };
+        let point = Position {
+            x: 5.0,
+            y: 3.0,
+            z: 0.0,
+        };
+        assert!(!is_in_swept_circle(
+            &ORIGIN,
+            &end,
+            1.0,
+            Some(&extent),
+            &point
+        ));
+        assert!(is_in_swept_circle(&ORIGIN, &end, 1.0, None, &point));
+    }
+
+    #[test]
+    fn swept_cone_hits_players_crossed_within_one_tick() {
+        let point = point_at(FRAC_PI_4, 5.0);
+        // Neither the start nor the end of the sweep covers the player
+        assert!(!cone(0.0, 0.0, &point));
+        assert!(!cone(FRAC_PI_2, FRAC_PI_2, &point));
+        assert!(cone(0.0, FRAC_PI_2, &point));
+        // Out of range, or outside the arc
+        assert!(!cone(0.0, FRAC_PI_2, &point_at(FRAC_PI_4, 11.0)));
+        assert!(!cone(0.0, FRAC_PI_2, &point_at(PI, 5.0)));
+    }
+
+    #[test]
+    fn swept_cone_wraps_around_past_pi() {
+        let start = 170f32.to_radians();
+        let end = 190f32.to_radians();
+        assert!(cone(start, end, &point_at(PI, 5.0)));
+        assert!(cone(start, end, &point_at(-175f32.to_radians(), 5.0)));
+        assert!(!cone(start, end, &point_at(-160f32.to_radians(), 5.0)));
+        // The same sweep, with rotations on either side of ±π
+        assert!(cone(
+            -190f32.to_radians(),
+            -170f32.to_radians(),
+            &point_at(PI, 5.0)
+        ));
+        // A whole turn or more covers everything in range
+        assert!(cone(start, end + TAU, &point_at(0.0, 5.0)));
+    }
+
+    #[test]
+    fn swept_cone_turns_either_way() {
+        let point = point_at(-FRAC_PI_4, 5.0);
+        assert!(cone(0.0, -FRAC_PI_2, &point));
+        assert!(cone(-FRAC_PI_2, 0.0, &point));
+        assert!(!cone(0.0, -FRAC_PI_2, &point_at(FRAC_PI_4, 5.0)));
+    }
+
+    #[test]
+    fn swept_line_hits_players_crossed_within_one_tick() {
+        let point = point_at(FRAC_PI_4, 5.0);
+        assert!(!line(0.0, 0.0, &point));
+        assert!(!line(FRAC_PI_2, FRAC_PI_2, &point));
+        assert!(line(0.0, FRAC_PI_2, &point));
+        // Past its end, or behind it
+        assert!(!line(0.0, FRAC_PI_2, &point_at(FRAC_PI_4, 11.0)));
+        assert!(!line(0.0, FRAC_PI_2, &point_at(FRAC_PI_4 + PI, 5.0)));
+        // Close enough to the center to be within its width, whatever its rotation
+        assert!(line(PI, PI, &point_at(0.0, 0.5)));
+    }
+
+    #[test]
+    fn swept_line_wraps_around_past_pi() {
+        let start = 170f32.to_radians();
+        let end = 190f32.to_radians();
+        assert!(line(start, end, &point_at(PI, 5.0)));
+        assert!(line(start, end, &point_at(-175f32.to_radians(), 5.0)));
+        assert!(!line(start, end, &point_at(-150f32.to_radians(), 5.0)));
+    }
+
+    #[test]
+    fn swept_line_turns_either_way() {
+        let point = point_at(-FRAC_PI_4, 5.0);
+        assert!(line(0.0, -FRAC_PI_2, &point));
+        assert!(line(-FRAC_PI_2, 0.0, &point));
+        assert!(!line(0.0, -FRAC_PI_2, &point_at(FRAC_PI_4, 5.0)));
+    }
+}
@@ -4,7 +4,7 @@ mod system_messages;
 mod webserver;
 
 use crate::system_messages::MessageToEcs;
-use std::sync::mpsc;
+use tokio::sync::mpsc;
 use tracing::info;
 use tracing_subscriber::FmtSubscriber;
 
@@ -18,26 +18,29 @@ use tracing_subscriber::FmtSubscriber;
 // However, this is only used when retrieving data from the ECS system for an HTTP request as this does lock the World to the accessing thread.
 // For most ECS operations that are triggered from a webserver event, messages are sent through a Channel.
 // The ECS system can then pick up these messages every game tick to process and affect the ECS World.
+// The channel is bounded, so a stalled ECS loop applies backpressure to the webserver instead of growing unbounded.
+// The ECS loop is supervised, and the server exits if it stops, rather than serving sockets with no game logic behind them.
+
+const ECS_CHANNEL_CAPACITY: usize = 4096;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     tracing::subscriber::set_global_default(FmtSubscriber::default())?;
 
-    let (tx_to_ecs, rx_from_ws) = mpsc::channel::<MessageToEcs>();
+    let (tx_to_ecs, rx_from_ws) = mpsc::channel::<MessageToEcs>(ECS_CHANNEL_CAPACITY);
 
     let (layer, io) = webserver::create_layer();
     let world = ecs_container::create_world();
 
-    ecs_container::run_world(world.clone(), rx_from_ws, &io);
+    let ecs_handle = ecs_container::run_world(world.clone(), rx_from_ws, &io);
 
     let name = env!("CARGO_PKG_NAME");
     let version = env!("CARGO_PKG_VERSION");
     info!("Starting {} v{}", name, version);
 
     // The webserver occupies the main thread
-    webserver::run_webserver(layer, io, tx_to_ecs, world)
-        .await
-        .unwrap();
-
-    Ok(())
+    tokio::select! {
+        result = webserver::run_webserver(layer, io, tx_to_ecs, world) => result,
+        result = ecs_container::supervise_world(ecs_handle) => result,
+    }
 }
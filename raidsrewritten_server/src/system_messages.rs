@@ -1,5 +1,9 @@
-use crate::game::{condition::Condition, role::Role};
+use crate::{
+    game::{condition::Condition, role::Role},
+    webserver::handshake::ClientCapabilities,
+};
 use socketioxide::socket::Sid;
+use std::time::Instant;
 
 pub enum MessageToEcs {
     UpdatePlayer {
@@ -11,14 +15,22 @@ pub enum MessageToEcs {
     },
     UpdateStatus {
         socket_id: Sid,
+        // When the webserver received the status, as it may wait in the channel before the ECS sees it
+        received_at: Instant,
         world_position_x: f32,
         world_position_y: f32,
         world_position_z: f32,
+        rotation: Option<f32>,
+        movement_flags: Option<u8>,
         is_alive: bool,
     },
     RemovePlayer {
         socket_id: Sid,
     },
+    CompleteHandshake {
+        socket_id: Sid,
+        capabilities: ClientCapabilities,
+    },
     StartMechanic {
         socket_id: Sid,
         request_id: String,
@@ -28,6 +40,8 @@ pub enum MessageToEcs {
         world_position_z: Option<f32>,
         rotation: Option<f32>,
         extra_data: Option<String>,
+        vertical_extent_below: Option<f32>,
+        vertical_extent_above: Option<f32>,
     },
     ClearMechanics {
         socket_id: Sid,
@@ -41,6 +55,14 @@ pub enum MessageToEcs {
     },
 }
 
+impl MessageToEcs {
+    // Status updates are sent many times a second, so the next one replaces any that's dropped.
+    // Everything else changes state that isn't sent again, and must reach the ECS.
+    pub fn is_best_effort(&self) -> bool {
+        matches!(self, MessageToEcs::UpdateStatus { .. })
+    }
+}
+
 pub struct ConditionDetails {
     pub id: u128,
     pub condition: Condition,
@@ -1,6 +1,9 @@
+pub mod handshake;
 pub mod message;
 pub mod metrics;
 pub mod network_mechanic;
+pub mod schema;
+pub mod validation;
 
 use crate::system_messages::{ConditionDetails, MessageToEcs};
 use crate::{game::components::*, webserver::metrics::*};
@@ -12,11 +15,13 @@ use socketioxide::{
     SocketIo,
     extract::{AckSender, Data, SocketRef},
     layer::SocketIoLayer,
-    socket::DisconnectReason,
+    socket::{DisconnectReason, Sid},
 };
-use std::sync::mpsc::Sender;
+use std::future::IntoFuture;
 use std::sync::{Arc, Mutex};
-use tracing::info;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::{Sender, error::SendTimeoutError};
+use tracing::{error, info, warn};
 
 async fn on_connect_impl(
     socket: SocketRef,
@@ -50,52 +55,86 @@ async fn on_message_impl(
 ) {
     // info!(?socket.id, "Received message\n{:#?}", message);
     // socket.emit("message-back", &message).ok();
+    let received_at = Instant::now();
+
+    let action: &'static str = (&message.action).into();
+    let message = match validation::validate_message(socket.id, message) {
+        Ok(m) => m,
+        Err(e) => {
+            warn!(?socket.id, action, reason = e.reason(), "Rejected inbound message");
+            INBOUND_MESSAGES_REJECTED
+                .with_label_values(&[action, e.reason()])
+                .inc();
+            return;
+        }
+    };
 
     match message.action {
         message::Action::UpdatePlayer => {
             if let Some(update_player) = message.update_player {
-                tx.send(MessageToEcs::UpdatePlayer {
-                    socket_id: socket.id,
-                    content_id: update_player.content_id,
-                    name: update_player.name,
-                    role: update_player.role,
-                    party: update_player.party,
-                })
-                .unwrap();
+                send_to_ecs(
+                    socket.id,
+                    &tx,
+                    MessageToEcs::UpdatePlayer {
+                        socket_id: socket.id,
+                        content_id: update_player.content_id,
+                        name: update_player.name,
+                        role: update_player.role,
+                        party: update_player.party,
+                    },
+                )
+                .await;
             }
         }
         message::Action::UpdateStatus => {
             if let Some(update_status) = message.update_status {
-                tx.send(MessageToEcs::UpdateStatus {
-                    socket_id: socket.id,
-                    world_position_x: update_status.world_position_x,
-                    world_position_y: update_status.world_position_y,
-                    world_position_z: update_status.world_position_z,
-                    is_alive: update_status.is_alive,
-                })
-                .unwrap();
+                send_to_ecs(
+                    socket.id,
+                    &tx,
+                    MessageToEcs::UpdateStatus {
+                        socket_id: socket.id,
+                        received_at,
+                        world_position_x: update_status.world_position_x,
+                        world_position_y: update_status.world_position_y,
+                        world_position_z: update_status.world_position_z,
+                        rotation: update_status.rotation,
+                        movement_flags: update_status.movement_flags,
+                        is_alive: update_status.is_alive,
+                    },
+                )
+                .await;
             }
         }
         message::Action::StartMechanic => {
             if let Some(start_mechanic) = message.start_mechanic {
-                tx.send(MessageToEcs::StartMechanic {
-                    socket_id: socket.id,
-                    request_id: start_mechanic.request_id.clone(),
-                    mechanic_id: start_mechanic.mechanic_id,
-                    world_position_x: start_mechanic.world_position_x,
-                    world_position_y: start_mechanic.world_position_y,
-                    world_position_z: start_mechanic.world_position_z,
-                    rotation: start_mechanic.rotation,
-                    extra_data: start_mechanic.extra_data,
-                })
-                .unwrap();
+                send_to_ecs(
+                    socket.id,
+                    &tx,
+                    MessageToEcs::StartMechanic {
+                        socket_id: socket.id,
+                        request_id: start_mechanic.request_id.clone(),
+                        mechanic_id: start_mechanic.mechanic_id,
+                        world_position_x: start_mechanic.world_position_x,
+                        world_position_y: start_mechanic.world_position_y,
+                        world_position_z: start_mechanic.world_position_z,
+                        rotation: start_mechanic.rotation,
+                        extra_data: start_mechanic.extra_data,
+                        vertical_extent_below: start_mechanic.vertical_extent_below,
+                        vertical_extent_above: start_mechanic.vertical_extent_above,
+                    },
+                )
+                .await;
             }
         }
         message::Action::ClearMechanics => {
-            tx.send(MessageToEcs::ClearMechanics {
-                socket_id: socket.id,
-            })
-            .unwrap();
+            send_to_ecs(
+                socket.id,
+                &tx,
+                MessageToEcs::ClearMechanics {
+                    socket_id: socket.id,
+                },
+            )
+            .await;
         }
         message::Action::SyncConditionsOnSelf => {
             if let Some(sync_conditions_on_self) = message.sync_conditions_on_self {
@@ -109,23 +148,103 @@ async fn on_message_impl(
                         newly_applied: c.newly_applied,
                     })
                     .collect();
-                tx.send(MessageToEcs::SyncConditionsOnSelf {
-                    socket_id: socket.id,
-                    conditions,
-                })
-                .unwrap();
+                send_to_ecs(
+                    socket.id,
+                    &tx,
+                    MessageToEcs::SyncConditionsOnSelf {
+                        socket_id: socket.id,
+                        conditions,
+                    },
+                )
+                .await;
             }
         }
         message::Action::ClearConditions => {
-            tx.send(MessageToEcs::ClearConditions {
-                socket_id: socket.id,
-            })
-            .unwrap();
+            send_to_ecs(
+                socket.id,
+                &tx,
+                MessageToEcs::ClearConditions {
+                    socket_id: socket.id,
+                },
+            )
+            .await;
+        }
+        message::Action::Handshake => {
+            if let Some(handshake) = message.handshake {
+                on_handshake(socket, handshake, &tx).await;
+            }
         }
         _ => {}
     }
 }
 
+async fn on_handshake(
+    socket: SocketRef,
+    handshake: message::HandshakePayload,
+    tx: &Sender<MessageToEcs>,
+) {
+    let result = handshake::negotiate(&handshake);
+    if !result.accepted {
+        warn!(
+            ?socket.id,
+            handshake.plugin_version,
+            handshake.protocol_version,
+            reason = result.reason,
+            "Handshake rejected"
+        );
+        HANDSHAKES.with_label_values(&["rejected"]).inc();
+        reject_socket(socket, result);
+        return;
+    }
+
+    info!(
+        ?socket.id,
+        handshake.plugin_version, handshake.protocol_version, "Handshake accepted"
+    );
+    HANDSHAKES.with_label_values(&["accepted"]).inc();
+    if let Err(e) = socket.emit(
+        "message",
+        &message::Message {
+            action: message::Action::HandshakeResult,
+            handshake_result: Some(result),
+            ..Default::default()
+        },
+    ) {
+        warn!(?socket.id, error = %e, "Failed to send handshake result");
+    }
+
+    send_to_ecs(
+        socket.id,
+        tx,
+        MessageToEcs::CompleteHandshake {
+            socket_id: socket.id,
+            capabilities: handshake::ClientCapabilities::from_handshake(&handshake),
+        },
+    )
+    .await;
+}
+
+// Emits are only queued, so disconnecting right away could drop the result before it's sent
+const REJECTED_DISCONNECT_DELAY: Duration = Duration::from_secs(1);
+
+// Tells the client why it was rejected, and then disconnects it
+pub fn reject_socket(socket: SocketRef, result: message::HandshakeResultPayload) {
+    if let Err(e) = socket.emit(
+        "message",
+        &message::Message {
+            action: message::Action::HandshakeResult,
+            handshake_result: Some(result),
+            ..Default::default()
+        },
+    ) {
+        warn!(?socket.id, error = %e, "Failed to send handshake result");
+    }
+    tokio::spawn(async move {
+        tokio::time::sleep(REJECTED_DISCONNECT_DELAY).await;
+        socket.disconnect().ok();
+    });
+}
+
 async fn on_message_with_ack(socket: SocketRef, Data(data): Data<Value>, ack: AckSender) {
     info!(?socket.id, ?data, "Received message-with-ack");
     ack.send(&data).ok();
@@ -135,10 +254,40 @@ async fn on_disconnect_impl(socket: SocketRef, reason: DisconnectReason, tx: Sen
     CONNECTED_CLIENTS.dec();
     info!(?socket.id, ?reason, "Socket disconnected");
 
-    tx.send(MessageToEcs::RemovePlayer {
-        socket_id: socket.id,
-    })
-    .unwrap();
+    send_to_ecs(
+        socket.id,
+        &tx,
+        MessageToEcs::RemovePlayer {
+            socket_id: socket.id,
+        },
+    )
+    .await;
+}
+
+// Best-effort messages wait for space in the ECS channel for at most this long before being dropped,
+// so a stalled ECS loop applies backpressure to sockets without blocking them forever.
+// Other messages wait until there's space, as dropping them would leave the ECS out of sync with the socket.
+const ECS_SEND_TIMEOUT: Duration = Duration::from_secs(1);
+
+async fn send_to_ecs(socket_id: Sid, tx: &Sender<MessageToEcs>, message: MessageToEcs) {
+    let result = if message.is_best_effort() {
+        tx.send_timeout(message, ECS_SEND_TIMEOUT).await
+    } else {
+        tx.send(message)
+            .await
+            .map_err(|e| SendTimeoutError::Closed(e.0))
+    };
+    match result {
+        Ok(()) => {}
+        Err(SendTimeoutError::Timeout(_)) => {
+            warn!(?socket_id, "ECS channel is full, dropping message");
+            ECS_MESSAGES_DROPPED.with_label_values(&["timeout"]).inc();
+        }
+        Err(SendTimeoutError::Closed(_)) => {
+            error!(?socket_id, "ECS channel is closed, dropping message");
+            ECS_MESSAGES_DROPPED.with_label_values(&["closed"]).inc();
+        }
+    }
 }
 
 pub fn create_layer() -> (SocketIoLayer, SocketIo) {
@@ -165,21 +314,19 @@ pub async fn run_webserver(
     let app = Router::new()
         .route("/", get(get_root))
         .route("/status", get(|| async move { get_status(&world) }))
+        .route("/protocol", get(schema::get_protocol))
         .layer(middleware::from_fn(metrics::metrics_middleware))
         .layer(socket_layer);
 
     let metrics_app = Router::new().route("/metrics", get(metrics::get_metrics));
 
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await.unwrap();
-    let t_app = tokio::task::spawn(async move { axum::serve(listener, app).await.unwrap() });
-
-    let listener_metrics = tokio::net::TcpListener::bind("0.0.0.0:3001").await.unwrap();
-    let t_metrics =
-        tokio::task::spawn(
-            async move { axum::serve(listener_metrics, metrics_app).await.unwrap() },
-        );
+    let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await?;
+    let listener_metrics = tokio::net::TcpListener::bind("0.0.0.0:3001").await?;
 
-    let _ = tokio::join!(t_app, t_metrics);
+    tokio::try_join!(
+        axum::serve(listener, app).into_future(),
+        axum::serve(listener_metrics, metrics_app).into_future()
+    )?;
 
     Ok(())
 }
@@ -195,10 +342,16 @@ fn get_status(world: &Arc<Mutex<World>>) -> String {
     let mut builder = string_builder::Builder::default();
     let mut players = 0;
     let world = world.lock().unwrap();
-    world.query::<(&Player, &Party)>().build().each(|(pl, pa)| {
-        builder.append(format!("{} - {}\n", pa.id, pl.name));
-        players += 1;
-    });
+    world
+        .query::<(&Player, &Party)>()
+        .term_at(1)
+        .up()
+        .build()
+        .each_entity(|e, (pl, pa)| {
+            let stale = if e.has(Stale::id()) { " (stale)" } else { "" };
+            builder.append(format!("{} - {}{}\n", pa.id, pl.name, stale));
+            players += 1;
+        });
     format!(
         "Players connected: {}\n\n{}",
         players,
@@ -0,0 +1,338 @@
+use crate::{
+    game::{condition::Condition, mechanics::MECHANICS},
+    webserver::{
+        message::{Action, HandshakePayload, HandshakeResultPayload, Message},
+        network_mechanic::NetworkMechanicCommand,
+    },
+};
+use strum::IntoEnumIterator;
+
+// Bump PROTOCOL_VERSION whenever the message format changes in a way older clients can't handle.
+// Clients below MIN_PROTOCOL_VERSION, or above PROTOCOL_VERSION, are rejected outright.
+// Whether each socket completed a handshake, and what it supports, is tracked in the ECS as ClientHandshakes.
+//
+// Legacy clients that never send a handshake are only served while REQUIRE_HANDSHAKE_ENV isn't set,
+// and are only sent what the last plugin release before the handshake understood, as ClientCapabilities::legacy.
+// Once it's set, their UpdatePlayer and StartMechanic messages are rejected and they're disconnected.
+//
+// Changelog:
+// 1: Handshake
+// 2: PlayTetherVfx
+// 3: Batch is optional, and only sent to clients that list it
+// 4: Handshake lists supported mechanic commands, and clients newer than the server are rejected
+// 5: PlayTetherVfx is optional, and only sent to clients that list it
+pub const PROTOCOL_VERSION: u32 = 5;
+pub const MIN_PROTOCOL_VERSION: u32 = 1;
+
+pub const REQUIRE_HANDSHAKE_ENV: &str = "REQUIRE_HANDSHAKE";
+
+// To-client actions that clients may leave out of their handshake. Sockets that don't list them aren't sent them.
+const OPTIONAL_ACTIONS: [Action; 2] = [Action::Batch, Action::PlayTetherVfx];
+
+// What plugin releases from before the handshake can handle
+const LEGACY_ACTIONS: [Action; 8] = [
+    Action::ApplyCondition,
+    Action::UpdatePartyStatus,
+    Action::PlayStaticVfx,
+    Action::PlayActorVfxOnTarget,
+    Action::PlayActorVfxOnPosition,
+    Action::StopVfx,
+    Action::UpdateConditions,
+    Action::RunMechanicCommand,
+];
+const LEGACY_CONDITIONS: [Condition; 10] = [
+    Condition::Stun,
+    Condition::Paralysis,
+    Condition::Bind,
+    Condition::Heavy,
+    Condition::Hysteria,
+    Condition::Pacify,
+    Condition::Sleep,
+    Condition::Knockback,
+    Condition::FireResistanceDown,
+    Condition::Flattened,
+];
+const LEGACY_MECHANIC_COMMANDS: [NetworkMechanicCommand; 6] = [
+    NetworkMechanicCommand::TeaShowShanoa,
+    NetworkMechanicCommand::TeaShowShanoaGuidanceMarkers,
+    NetworkMechanicCommand::TeaMoveShanoa,
+    NetworkMechanicCommand::TeaFireTornadoAttackShanoa,
+    NetworkMechanicCommand::TeaShanoaRunsAway,
+    NetworkMechanicCommand::TeaShanoaAbsorbsMarker,
+];
+
+// What a socket reported it can handle in its handshake
+#[derive(Debug, Default)]
+pub struct ClientCapabilities {
+    pub actions: Vec<u32>,
+    pub conditions: Vec<u32>,
+    pub mechanic_commands: Vec<i32>,
+}
+
+impl ClientCapabilities {
+    pub fn from_handshake(handshake: &HandshakePayload) -> Self {
+        Self {
+            actions: handshake.supported_actions.clone(),
+            conditions: handshake.supported_conditions.clone(),
+            mechanic_commands: handshake.supported_mechanic_commands.clone(),
+        }
+    }
+
+    pub fn legacy() -> Self {
+        Self {
+            actions: LEGACY_ACTIONS.iter().map(|a| *a as u32).collect(),
+            conditions: LEGACY_CONDITIONS.iter().map(|c| *c as u32).collect(),
+            mechanic_commands: LEGACY_MECHANIC_COMMANDS.iter().map(|c| *c as i32).collect(),
+        }
+    }
+
+    pub fn supports_action(&self, action: Action) -> bool {
+        self.actions.contains(&(action as u32))
+    }
+
+    // The message with anything the socket can't handle removed, or None if none of it can be sent
+    pub fn filter_message(&self, mut message: Message) -> Option<Message> {
+        if !self.supports_action(message.action) {
+            return None;
+        }
+        if let Some(payload) = &message.apply_condition
+            && !self.conditions.contains(&(payload.condition as u32))
+        {
+            return None;
+        }
+        if let Some(payload) = &message.run_mechanic_command
+            && !self
+                .mechanic_commands
+                .contains(&payload.mechanic_command_id)
+        {
+            return None;
+        }
+        if let Some(payload) = &mut message.update_conditions {
+            for player in &mut payload.players {
+                player
+                    .conditions
+                    .retain(|c| self.conditions.contains(&(c.condition as u32)));
+            }
+        }
+        Some(message)
+    }
+}
+
+pub fn is_handshake_required() -> bool {
+    std::env::var(REQUIRE_HANDSHAKE_ENV).is_ok_and(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+}
+
+fn server_capabilities() -> HandshakeResultPayload {
+    HandshakeResultPayload {
+        accepted: true,
+        reason: None,
+        server_version: env!("CARGO_PKG_VERSION").to_string(),
+        protocol_version: PROTOCOL_VERSION,
+        supported_actions: Action::iter()
+            .filter(|a| *a != Action::None)
+            .map(|a| a as u32)
+            .collect(),
+        supported_conditions: Condition::iter()
+            .filter(|c| *c != Condition::None)
+            .map(|c| c as u32)
+            .collect(),
+        supported_mechanics: MECHANICS.iter().map(|(id, _)| *id).collect(),
+        supported_mechanic_commands: NetworkMechanicCommand::iter().map(|c| c as i32).collect(),
+    }
+}
+
+pub fn rejection(reason: String) -> HandshakeResultPayload {
+    HandshakeResultPayload {
+        accepted: false,
+        reason: Some(reason),
+        ..server_capabilities()
+    }
+}
+
+pub fn negotiate(handshake: &HandshakePayload) -> HandshakeResultPayload {
+    if handshake.protocol_version < MIN_PROTOCOL_VERSION {
+        return rejection(format!(
+            "Protocol version {} is no longer supported, minimum is {}",
+            handshake.protocol_version, MIN_PROTOCOL_VERSION
+        ));
+    }
+    if handshake.protocol_version > PROTOCOL_VERSION {
+        return rejection(format!(
+            "Protocol version {} is newer than the server's {}",
+            handshake.protocol_version, PROTOCOL_VERSION
+        ));
+    }
+
+    // The client must be able to handle everything the server may send it, except the optional actions
+    let missing_actions: Vec<&str> = Action::iter()
+        .filter(|a| {
+            a.is_to_client()
+                && !OPTIONAL_ACTIONS.contains(a)
+                && !handshake.supported_actions.contains(&(*a as u32))
+        })
+        .map(|a| a.into())
+        .collect();
+    let missing_conditions: Vec<&str> = Condition::iter()
+        .filter(|c| *c != Condition::None && !handshake.supported_conditions.contains(&(*c as u32)))
+        .map(|c| c.into())
+        .collect();
+    let missing_mechanic_commands: Vec<&str> = NetworkMechanicCommand::iter()
+        .filter(|c| !handshake.supported_mechanic_commands.contains(&(*c as i32)))
+        .map(|c| c.into())
+        .collect();
+
+    if !missing_actions.is_empty()
+        || !missing_conditions.is_empty()
+        || !missing_mechanic_commands.is_empty()
+    {
+        return rejection(format!(
+            "Unsupported actions: [{}], unsupported conditions: [{}], unsupported mechanic commands: [{}]",
+            missing_actions.join(", "),
+            missing_conditions.join(", "),
+            missing_mechanic_commands.join(", ")
+        ));
+    }
+
+    server_capabilities()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::webserver::message::{
+        ApplyConditionPayload, RunMechanicCommandPayload, UpdateConditionsConditionDetails,
+        UpdateConditionsPayload, UpdateConditionsPlayer,
+    };
+
+    fn handshake(protocol_version: u32) -> HandshakePayload {
+        let server = server_capabilities();
+        HandshakePayload {
+            plugin_version: "1.0.0".to_string(),
+            protocol_version,
+            supported_actions: server.supported_actions,
+            supported_conditions: server.supported_conditions,
+            supported_mechanic_commands: server.supported_mechanic_commands,
+        }
+    }
+
+    #[test]
+    fn current_client_is_accepted() {
+        assert!(negotiate(&handshake(PROTOCOL_VERSION)).accepted);
+    }
+
+    #[test]
+    fn unsupported_protocol_version_is_rejected() {
+        assert!(!negotiate(&handshake(MIN_PROTOCOL_VERSION - 1)).accepted);
+        assert!(!negotiate(&handshake(PROTOCOL_VERSION + 1)).accepted);
+    }
+
+    #[test]
+    fn optional_actions_can_be_left_out() {
+        let mut hs = handshake(PROTOCOL_VERSION);
+        hs.supported_actions
+            .retain(|a| *a != Action::Batch as u32 && *a != Action::PlayTetherVfx as u32);
+        assert!(negotiate(&hs).accepted);
+
+        hs.supported_actions
+            .retain(|a| *a != Action::StopVfx as u32);
+        assert!(!negotiate(&hs).accepted);
+    }
+
+    #[test]
+    fn missing_mechanic_commands_are_rejected() {
+        let mut hs = handshake(PROTOCOL_VERSION);
+        hs.supported_mechanic_commands.pop();
+        let result = negotiate(&hs);
+        assert!(!result.accepted);
+        assert!(
+            result
+                .reason
+                .unwrap()
+                .contains("unsupported mechanic commands")
+        );
+    }
+
+    #[test]
+    fn legacy_clients_are_not_sent_what_they_cannot_handle() {
+        let legacy = ClientCapabilities::legacy();
+        let message = |action| Message {
+            action,
+            ..Default::default()
+        };
+        assert!(legacy.filter_message(message(Action::StopVfx)).is_some());
+        assert!(legacy.filter_message(message(Action::Batch)).is_none());
+        assert!(
+            legacy
+                .filter_message(message(Action::PlayTetherVfx))
+                .is_none()
+        );
+
+        let command = |id| Message {
+            action: Action::RunMechanicCommand,
+            run_mechanic_command: Some(RunMechanicCommandPayload {
+                mechanic_command_id: id,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let tea = NetworkMechanicCommand::TeaMoveShanoa as i32;
+        let projectile = NetworkMechanicCommand::SpawnProjectile as i32;
+        assert!(legacy.filter_message(command(tea)).is_some());
+        assert!(legacy.filter_message(command(projectile)).is_none());
+    }
+
+    #[test]
+    fn unsupported_conditions_are_left_out() {
+        let capabilities = ClientCapabilities {
+            actions: vec![
+                Action::ApplyCondition as u32,
+                Action::UpdateConditions as u32,
+            ],
+            conditions: vec![Condition::Stun as u32],
+            ..Default::default()
+        };
+
+        let apply = |condition| Message {
+            action: Action::ApplyCondition,
+            apply_condition: Some(ApplyConditionPayload {
+                condition,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        assert!(
+            capabilities
+                .filter_message(apply(Condition::Stun))
+                .is_some()
+        );
+        assert!(
+            capabilities
+                .filter_message(apply(Condition::Bind))
+                .is_none()
+        );
+
+        let update = Message {
+            action: Action::UpdateConditions,
+            update_conditions: Some(UpdateConditionsPayload {
+                players: vec![UpdateConditionsPlayer {
+                    content_id: 0,
+                    conditions: [Condition::Stun, Condition::Bind]
+                        .into_iter()
+                        .map(|condition| UpdateConditionsConditionDetails {
+                            condition,
+                            ..Default::default()
+                        })
+                        .collect(),
+                }],
+            }),
+            ..Default::default()
+        };
+        let update = capabilities.filter_message(update).unwrap();
+        let conditions: Vec<Condition> = update.update_conditions.unwrap().players[0]
+            .conditions
+            .iter()
+            .map(|c| c.condition)
+            .collect();
+        assert_eq!(conditions, vec![Condition::Stun]);
+    }
+}
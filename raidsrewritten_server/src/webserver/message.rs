@@ -1,9 +1,21 @@
 use crate::game::{condition::Condition, role::Role};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use serde_repr::*;
 use serde_with::{BoolFromInt, formats::Flexible, serde_as};
+use strum_macros::{EnumIter, IntoStaticStr};
 
-#[derive(Serialize_repr, Deserialize_repr, PartialEq, Default, Debug)]
+#[derive(
+    Serialize_repr,
+    Deserialize_repr,
+    PartialEq,
+    IntoStaticStr,
+    EnumIter,
+    Default,
+    Copy,
+    Clone,
+    Debug,
+)]
 #[repr(u32)]
 pub enum Action {
     #[default]
@@ -16,6 +28,7 @@ pub enum Action {
     ClearMechanics = 4,
     SyncConditionsOnSelf = 5,
     ClearConditions = 6,
+    Handshake = 7,
 
     // To client
     // Deprecated: 51, 55
@@ -27,10 +40,19 @@ pub enum Action {
     StopVfx = 58,
     UpdateConditions = 59,
     RunMechanicCommand = 60,
+    HandshakeResult = 61,
+    Batch = 62,
+    PlayTetherVfx = 63,
+}
+
+impl Action {
+    pub fn is_to_client(self) -> bool {
+        self as u32 > 50
+    }
 }
 
 #[serde_with::skip_serializing_none]
-#[derive(Serialize, Deserialize, Default, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Default, Debug)]
 pub struct Message {
     #[serde(rename = "a")]
     pub action: Action,
@@ -44,6 +66,8 @@ pub struct Message {
     pub start_mechanic: Option<StartMechanicPayload>,
     #[serde(rename = "scos")]
     pub sync_conditions_on_self: Option<SyncConditionsOnSelfPayload>,
+    #[serde(rename = "hs")]
+    pub handshake: Option<HandshakePayload>,
 
     // To client
     #[serde(rename = "ac")]
@@ -56,17 +80,23 @@ pub struct Message {
     pub play_actor_vfx_on_target: Option<PlayActorVfxOnTargetPayload>,
     #[serde(rename = "pavp")]
     pub play_actor_vfx_on_position: Option<PlayActorVfxOnPositionPayload>,
+    #[serde(rename = "ptv")]
+    pub play_tether_vfx: Option<PlayTetherVfxPayload>,
     #[serde(rename = "sv")]
     pub stop_vfx: Option<StopVfxPayload>,
     #[serde(rename = "uc")]
     pub update_conditions: Option<UpdateConditionsPayload>,
     #[serde(rename = "rmc")]
     pub run_mechanic_command: Option<RunMechanicCommandPayload>,
+    #[serde(rename = "hsr")]
+    pub handshake_result: Option<HandshakeResultPayload>,
+    #[serde(rename = "b")]
+    pub batch: Option<BatchPayload>,
 }
 
 // To server ===============
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
 pub struct UpdatePlayerPayload {
     #[serde(rename = "contentId")]
     pub content_id: u64,
@@ -76,7 +106,7 @@ pub struct UpdatePlayerPayload {
 }
 
 #[serde_as]
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
 pub struct UpdateStatusPayload {
     #[serde(rename = "x")]
     pub world_position_x: f32,
@@ -87,9 +117,15 @@ pub struct UpdateStatusPayload {
     #[serde(rename = "a")]
     #[serde_as(as = "BoolFromInt<Flexible>")]
     pub is_alive: bool,
+    // Facing direction in radians, optional for clients that don't report it
+    #[serde(rename = "r", default)]
+    pub rotation: Option<f32>,
+    // Bitfield of MovementFlags, optional for clients that don't report it
+    #[serde(rename = "mf", default)]
+    pub movement_flags: Option<u8>,
 }
 
-#[derive(Serialize, Deserialize, Default, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Default, Debug)]
 pub struct StartMechanicPayload {
     #[serde(rename = "ri")]
     pub request_id: String,
@@ -105,15 +141,20 @@ pub struct StartMechanicPayload {
     pub rotation: Option<f32>,
     #[serde(rename = "ed")]
     pub extra_data: Option<String>,
+    // How far below and above the mechanic's y its AoEs reach. Both must be set for the AoEs to be height-aware.
+    #[serde(rename = "vb", default)]
+    pub vertical_extent_below: Option<f32>,
+    #[serde(rename = "va", default)]
+    pub vertical_extent_above: Option<f32>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
 pub struct SyncConditionsOnSelfPayload {
     #[serde(rename = "c")]
     pub conditions: Vec<SyncConditionsOnSelfConditionDetails>,
 }
 #[serde_as]
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
 pub struct SyncConditionsOnSelfConditionDetails {
     pub id: u128,
     #[serde(rename = "c")]
@@ -125,9 +166,24 @@ pub struct SyncConditionsOnSelfConditionDetails {
     pub newly_applied: bool,
 }
 
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct HandshakePayload {
+    #[serde(rename = "pv")]
+    pub plugin_version: String,
+    #[serde(rename = "p")]
+    pub protocol_version: u32,
+    #[serde(rename = "sa")]
+    pub supported_actions: Vec<u32>,
+    #[serde(rename = "sc")]
+    pub supported_conditions: Vec<u32>,
+    // NetworkMechanicCommand ids, left out by clients before protocol version 4
+    #[serde(rename = "smc", default)]
+    pub supported_mechanic_commands: Vec<i32>,
+}
+
 // To client ===============
 
-#[derive(Serialize, Deserialize, Default, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Default, Debug)]
 pub struct ApplyConditionPayload {
     #[serde(rename = "c")]
     pub condition: Condition,
@@ -139,13 +195,16 @@ pub struct ApplyConditionPayload {
     pub knockback_direction_z: Option<f32>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
 pub struct UpdatePartyStatusPayload {
     #[serde(rename = "c")]
     pub connected_players_in_party: u8,
+    // Content ids of connected players whose position hasn't been updated recently
+    #[serde(rename = "s", default, skip_serializing_if = "Vec::is_empty")]
+    pub stale_players: Vec<u64>,
 }
 
-#[derive(Serialize, Deserialize, Default, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Default, Debug)]
 pub struct PlayStaticVfxPayload {
     pub id: u128,
     #[serde(rename = "v")]
@@ -168,7 +227,7 @@ pub struct PlayStaticVfxPayload {
     pub scale_z: Option<f32>,
 }
 
-#[derive(Serialize, Deserialize, Default, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Default, Debug)]
 pub struct PlayActorVfxOnTargetPayload {
     #[serde(rename = "v")]
     pub vfx_path: String,
@@ -178,7 +237,7 @@ pub struct PlayActorVfxOnTargetPayload {
     pub custom_id_targets: Vec<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
 pub struct PlayActorVfxOnPositionPayload {
     #[serde(rename = "v")]
     pub vfx_path: String,
@@ -192,24 +251,36 @@ pub struct PlayActorVfxOnPositionPayload {
     pub rotation: f32,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+// A vfx stretched from one player to another, which follows both until it's stopped
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct PlayTetherVfxPayload {
+    pub id: u128,
+    #[serde(rename = "v")]
+    pub vfx_path: String,
+    #[serde(rename = "s")]
+    pub content_id_source: u64,
+    #[serde(rename = "t")]
+    pub content_id_target: u64,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
 pub struct StopVfxPayload {
     pub id: u128,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
 pub struct UpdateConditionsPayload {
     #[serde(rename = "p")]
     pub players: Vec<UpdateConditionsPlayer>,
 }
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
 pub struct UpdateConditionsPlayer {
     #[serde(rename = "i")]
     pub content_id: u64,
     #[serde(rename = "c")]
     pub conditions: Vec<UpdateConditionsConditionDetails>,
 }
-#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Default, Debug)]
 pub struct UpdateConditionsConditionDetails {
     pub id: u128,
     #[serde(rename = "c")]
@@ -233,9 +304,12 @@ pub struct UpdateConditionsConditionDetails {
 
     #[serde(rename = "hri")]
     pub hysteria_redirection_interval: Option<f32>,
+
+    #[serde(rename = "st")]
+    pub stacks: Option<u8>,
 }
 
-#[derive(Serialize, Deserialize, Default, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Default, Debug)]
 pub struct RunMechanicCommandPayload {
     #[serde(rename = "i")]
     pub mechanic_command_id: i32,
@@ -250,3 +324,30 @@ pub struct RunMechanicCommandPayload {
     #[serde(rename = "ed")]
     pub extra_data: Option<String>,
 }
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Default, Debug)]
+pub struct HandshakeResultPayload {
+    #[serde(rename = "a")]
+    pub accepted: bool,
+    #[serde(rename = "r")]
+    pub reason: Option<String>,
+    #[serde(rename = "sv")]
+    pub server_version: String,
+    #[serde(rename = "p")]
+    pub protocol_version: u32,
+    #[serde(rename = "sa")]
+    pub supported_actions: Vec<u32>,
+    #[serde(rename = "sc")]
+    pub supported_conditions: Vec<u32>,
+    #[serde(rename = "sm")]
+    pub supported_mechanics: Vec<u32>,
+    #[serde(rename = "smc")]
+    pub supported_mechanic_commands: Vec<i32>,
+}
+
+// Messages are unpacked and handled in order
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Default, Debug)]
+pub struct BatchPayload {
+    #[serde(rename = "m")]
+    pub messages: Vec<Message>,
+}
@@ -30,6 +30,29 @@ lazy_static! {
         &["mechanic_id"]
     )
     .expect("metric can be created");
+    pub static ref INBOUND_MESSAGES_REJECTED: IntCounterVec = IntCounterVec::new(
+        Opts::new("inbound_messages_rejected", "Inbound Messages Rejected"),
+        &["action", "reason"]
+    )
+    .expect("metric can be created");
+    pub static ref INBOUND_MESSAGES_CLAMPED: IntCounterVec = IntCounterVec::new(
+        Opts::new("inbound_messages_clamped", "Inbound Messages Clamped"),
+        &["action", "reason"]
+    )
+    .expect("metric can be created");
+    pub static ref ECS_MESSAGES_DROPPED: IntCounterVec = IntCounterVec::new(
+        Opts::new("ecs_messages_dropped", "Messages To ECS Dropped"),
+        &["reason"]
+    )
+    .expect("metric can be created");
+    pub static ref HANDSHAKES: IntCounterVec =
+        IntCounterVec::new(Opts::new("handshakes", "Client Handshakes"), &["result"])
+            .expect("metric can be created");
+    pub static ref OUTBOUND_MESSAGES_FAILED: IntCounterVec = IntCounterVec::new(
+        Opts::new("outbound_messages_failed", "Outbound Messages Failed"),
+        &["reason"]
+    )
+    .expect("metric can be created");
 }
 
 pub fn init_metrics() {
@@ -51,6 +74,21 @@ pub fn init_metrics() {
     REGISTRY
         .register(Box::new(MECHANICS_STARTED.clone()))
         .expect("collector can be registered");
+    REGISTRY
+        .register(Box::new(INBOUND_MESSAGES_REJECTED.clone()))
+        .expect("collector can be registered");
+    REGISTRY
+        .register(Box::new(INBOUND_MESSAGES_CLAMPED.clone()))
+        .expect("collector can be registered");
+    REGISTRY
+        .register(Box::new(ECS_MESSAGES_DROPPED.clone()))
+        .expect("collector can be registered");
+    REGISTRY
+        .register(Box::new(HANDSHAKES.clone()))
+        .expect("collector can be registered");
+    REGISTRY
+        .register(Box::new(OUTBOUND_MESSAGES_FAILED.clone()))
+        .expect("collector can be registered");
 }
 
 // https://oneuptime.com/blog/post/2026-01-07-rust-prometheus-custom-metrics/view
@@ -1,5 +1,18 @@
+use serde_repr::Serialize_repr;
+use strum_macros::{EnumIter, IntoStaticStr};
+
+#[derive(Serialize_repr, IntoStaticStr, EnumIter, Copy, Clone, Debug)]
+#[allow(clippy::enum_variant_names)]
 #[repr(i32)]
 pub enum NetworkMechanicCommand {
+    // Projectiles, which any mechanic can spawn
+    SpawnProjectile = -1,
+    MoveProjectile = -2,
+    DespawnProjectile = -3,
+    // Rotating sweep
+    RotatingSweepStart = -110,
+    RotatingSweepStop = -111,
+    // TEA
     TeaShowShanoa = -1020,
     TeaShowShanoaGuidanceMarkers = -1021,
     TeaMoveShanoa = -1022,
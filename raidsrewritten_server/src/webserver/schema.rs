@@ -0,0 +1,81 @@
+use crate::{
+    game::{condition::Condition, role::Role},
+    webserver::{
+        handshake::PROTOCOL_VERSION,
+        message::{Action, Message},
+        network_mechanic::NetworkMechanicCommand,
+    },
+};
+use axum::Json;
+use schemars::{JsonSchema, Schema, SchemaGenerator, json_schema, schema_for};
+use serde::Serialize;
+use serde_json::{Map, Value, json};
+use std::borrow::Cow;
+use strum::IntoEnumIterator;
+
+// The protocol schema is generated from the serde types in message.rs, so it always matches what the server
+// actually (de)serializes. Clients can diff against this instead of mirroring the Rust structs by hand.
+
+// serde_repr enums are sent as their integer values, which the JsonSchema derive can't describe,
+// so their schemas are built from the same integer values serde produces.
+macro_rules! impl_repr_enum_schema {
+    ($t:ty) => {
+        impl JsonSchema for $t {
+            fn schema_name() -> Cow<'static, str> {
+                stringify!($t).into()
+            }
+
+            fn json_schema(_: &mut SchemaGenerator) -> Schema {
+                let one_of: Vec<Value> = enum_entries::<$t>()
+                    .into_iter()
+                    .map(|(name, value)| json!({ "const": value, "title": name }))
+                    .collect();
+                json_schema!({
+                    "type": "integer",
+                    "oneOf": one_of,
+                })
+            }
+        }
+    };
+}
+
+impl_repr_enum_schema!(Action);
+impl_repr_enum_schema!(Condition);
+impl_repr_enum_schema!(Role);
+
+fn enum_entries<E>() -> Vec<(String, Value)>
+where
+    E: IntoEnumIterator + Serialize + Into<&'static str>,
+{
+    E::iter()
+        .map(|e| {
+            let value = serde_json::to_value(&e).unwrap_or(Value::Null);
+            (Into::<&'static str>::into(e).to_string(), value)
+        })
+        .collect()
+}
+
+fn enum_table<E>() -> Map<String, Value>
+where
+    E: IntoEnumIterator + Serialize + Into<&'static str>,
+{
+    enum_entries::<E>().into_iter().collect()
+}
+
+pub fn build_protocol() -> Value {
+    json!({
+        "serverVersion": env!("CARGO_PKG_VERSION"),
+        "protocolVersion": PROTOCOL_VERSION,
+        "message": schema_for!(Message),
+        "enums": {
+            "Action": enum_table::<Action>(),
+            "Condition": enum_table::<Condition>(),
+            "Role": enum_table::<Role>(),
+            "NetworkMechanicCommand": enum_table::<NetworkMechanicCommand>(),
+        },
+    })
+}
+
+pub async fn get_protocol() -> Json<Value> {
+    Json(build_protocol())
+}
@@ -0,0 +1,415 @@
+use crate::{
+    game::components::MovementFlags,
+    webserver::{message::*, metrics::*},
+};
+use socketioxide::socket::Sid;
+use std::fmt;
+use tracing::warn;
+
+// Inbound payloads are validated here before they are converted into MessageToEcs.
+// Malformed data that cannot be sensibly corrected rejects the whole message,
+// while out-of-range values that can be corrected are clamped.
+// Both outcomes are logged and counted in metrics.
+
+const MAX_NAME_LENGTH: usize = 32;
+const MAX_PARTY_LENGTH: usize = 64;
+const MAX_REQUEST_ID_LENGTH: usize = 64;
+const MAX_EXTRA_DATA_LENGTH: usize = 1024;
+const MAX_WORLD_COORDINATE: f32 = 10_000.0;
+const MAX_CONDITIONS: usize = 64;
+const MAX_CONDITION_TIME_REMAINING: f32 = 3600.0;
+const MAX_PLUGIN_VERSION_LENGTH: usize = 32;
+const MAX_CAPABILITIES: usize = 256;
+
+#[derive(Debug, PartialEq)]
+pub enum ValidationError {
+    MissingPayload,
+    EmptyName,
+    EmptyParty,
+    PartyTooLong,
+    EmptyRequestId,
+    RequestIdTooLong,
+    ExtraDataTooLong,
+    NonFinitePosition,
+    NonFiniteRotation,
+    InvalidVerticalExtent,
+    PluginVersionTooLong,
+    TooManyCapabilities,
+}
+
+impl ValidationError {
+    pub fn reason(&self) -> &'static str {
+        match self {
+            ValidationError::MissingPayload => "missing_payload",
+            ValidationError::EmptyName => "empty_name",
+            ValidationError::EmptyParty => "empty_party",
+            ValidationError::PartyTooLong => "party_too_long",
+            ValidationError::EmptyRequestId => "empty_request_id",
+            ValidationError::RequestIdTooLong => "request_id_too_long",
+            ValidationError::ExtraDataTooLong => "extra_data_too_long",
+            ValidationError::NonFinitePosition => "non_finite_position",
+            ValidationError::NonFiniteRotation => "non_finite_rotation",
+            ValidationError::InvalidVerticalExtent => "invalid_vertical_extent",
+            ValidationError::PluginVersionTooLong => "plugin_version_too_long",
+            ValidationError::TooManyCapabilities => "too_many_capabilities",
+        }
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.reason())
+    }
+}
+
+pub fn validate_message(socket_id: Sid, mut message: Message) -> Result<Message, ValidationError> {
+    let action: &'static str = (&message.action).into();
+    match message.action {
+        Action::UpdatePlayer => {
+            let payload = message
+                .update_player
+                .as_mut()
+                .ok_or(ValidationError::MissingPayload)?;
+            validate_update_player(socket_id, action, payload)?;
+        }
+        Action::UpdateStatus => {
+            let payload = message
+                .update_status
+                .as_mut()
+                .ok_or(ValidationError::MissingPayload)?;
+            validate_update_status(socket_id, action, payload)?;
+        }
+        Action::StartMechanic => {
+            let payload = message
+                .start_mechanic
+                .as_mut()
+                .ok_or(ValidationError::MissingPayload)?;
+            validate_start_mechanic(payload)?;
+        }
+        Action::SyncConditionsOnSelf => {
+            let payload = message
+                .sync_conditions_on_self
+                .as_mut()
+                .ok_or(ValidationError::MissingPayload)?;
+            validate_sync_conditions_on_self(socket_id, action, payload);
+        }
+        Action::Handshake => {
+            let payload = message
+                .handshake
+                .as_ref()
+                .ok_or(ValidationError::MissingPayload)?;
+            validate_handshake(payload)?;
+        }
+        _ => {}
+    }
+    Ok(message)
+}
+
+fn validate_update_player(
+    socket_id: Sid,
+    action: &'static str,
+    payload: &mut UpdatePlayerPayload,
+) -> Result<(), ValidationError> {
+    let name = payload.name.trim();
+    if name.is_empty() {
+        return Err(ValidationError::EmptyName);
+    }
+    if name.chars().count() > MAX_NAME_LENGTH {
+        on_clamped(socket_id, action, "name_too_long");
+        payload.name = name.chars().take(MAX_NAME_LENGTH).collect();
+    } else if name.len() != payload.name.len() {
+        payload.name = name.to_string();
+    }
+
+    let party = payload.party.trim();
+    if party.is_empty() {
+        return Err(ValidationError::EmptyParty);
+    }
+    if party.len() > MAX_PARTY_LENGTH {
+        return Err(ValidationError::PartyTooLong);
+    }
+    if party.len() != payload.party.len() {
+        payload.party = party.to_string();
+    }
+    Ok(())
+}
+
+fn validate_update_status(
+    socket_id: Sid,
+    action: &'static str,
+    payload: &mut UpdateStatusPayload,
+) -> Result<(), ValidationError> {
+    let coordinates = [
+        &mut payload.world_position_x,
+        &mut payload.world_position_y,
+        &mut payload.world_position_z,
+    ];
+    if coordinates.iter().any(|c| !c.is_finite()) {
+        return Err(ValidationError::NonFinitePosition);
+    }
+    for c in coordinates {
+        if c.abs() > MAX_WORLD_COORDINATE {
+            on_clamped(socket_id, action, "position_out_of_range");
+            *c = c.clamp(-MAX_WORLD_COORDINATE, MAX_WORLD_COORDINATE);
+        }
+    }
+    if payload.rotation.is_some_and(|r| !r.is_finite()) {
+        return Err(ValidationError::NonFiniteRotation);
+    }
+    if let Some(flags) = &mut payload.movement_flags
+        && *flags & !MovementFlags::ALL != 0
+    {
+        on_clamped(socket_id, action, "unknown_movement_flags");
+        *flags &= MovementFlags::ALL;
+    }
+    Ok(())
+}
+
+fn validate_start_mechanic(payload: &mut StartMechanicPayload) -> Result<(), ValidationError> {
+    if payload.request_id.is_empty() {
+        return Err(ValidationError::EmptyRequestId);
+    }
+    if payload.request_id.len() > MAX_REQUEST_ID_LENGTH {
+        return Err(ValidationError::RequestIdTooLong);
+    }
+    if payload
+        .extra_data
+        .as_ref()
+        .is_some_and(|ed| ed.len() > MAX_EXTRA_DATA_LENGTH)
+    {
+        return Err(ValidationError::ExtraDataTooLong);
+    }
+    if [
+        payload.world_position_x,
+        payload.world_position_y,
+        payload.world_position_z,
+    ]
+    .iter()
+    .flatten()
+    .any(|c| !c.is_finite() || c.abs() > MAX_WORLD_COORDINATE)
+    {
+        return Err(ValidationError::NonFinitePosition);
+    }
+    if payload.rotation.is_some_and(|r| !r.is_finite()) {
+        return Err(ValidationError::NonFiniteRotation);
+    }
+    if [payload.vertical_extent_below, payload.vertical_extent_above]
+        .iter()
+        .flatten()
+        .any(|e| !e.is_finite() || *e < 0.0 || *e > MAX_WORLD_COORDINATE)
+    {
+        return Err(ValidationError::InvalidVerticalExtent);
+    }
+    Ok(())
+}
+
+fn validate_sync_conditions_on_self(
+    socket_id: Sid,
+    action: &'static str,
+    payload: &mut SyncConditionsOnSelfPayload,
+) {
+    if payload.conditions.len() > MAX_CONDITIONS {
+        on_clamped(socket_id, action, "too_many_conditions");
+        payload.conditions.truncate(MAX_CONDITIONS);
+    }
+
+    payload.conditions.retain(|c| {
+        let valid = c.time_remaining.is_finite();
+        if !valid {
+            on_clamped(socket_id, action, "non_finite_time_remaining");
+        }
+        valid
+    });
+
+    for c in &mut payload.conditions {
+        if !(0.0..=MAX_CONDITION_TIME_REMAINING).contains(&c.time_remaining) {
+            on_clamped(socket_id, action, "time_remaining_out_of_range");
+            c.time_remaining = c.time_remaining.clamp(0.0, MAX_CONDITION_TIME_REMAINING);
+        }
+    }
+}
+
+fn validate_handshake(payload: &HandshakePayload) -> Result<(), ValidationError> {
+    if payload.plugin_version.len() > MAX_PLUGIN_VERSION_LENGTH {
+        return Err(ValidationError::PluginVersionTooLong);
+    }
+    if payload.supported_actions.len() > MAX_CAPABILITIES
+        || payload.supported_conditions.len() > MAX_CAPABILITIES
+        || payload.supported_mechanic_commands.len() > MAX_CAPABILITIES
+    {
+        return Err(ValidationError::TooManyCapabilities);
+    }
+    Ok(())
+}
+
+fn on_clamped(socket_id: Sid, action: &'static str, reason: &'static str) {
+    warn!(
+        socket_str = socket_id.as_str(),
+        action, reason, "Clamped inbound message field"
+    );
+    INBOUND_MESSAGES_CLAMPED
+        .with_label_values(&[action, reason])
+        .inc();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{condition::Condition, role::Role};
+
+    fn update_player(name: &str, party: &str) -> UpdatePlayerPayload {
+        UpdatePlayerPayload {
+            content_id: 1,
+            name: name.to_string(),
+            role: Role::Dps,
+            party: party.to_string(),
+        }
+    }
+
+    fn update_status(x: f32, y: f32, z: f32) -> UpdateStatusPayload {
+        UpdateStatusPayload {
+            world_position_x: x,
+            world_position_y: y,
+            world_position_z: z,
+            is_alive: true,
+            rotation: None,
+            movement_flags: None,
+        }
+    }
+
+    fn condition(time_remaining: f32) -> SyncConditionsOnSelfConditionDetails {
+        SyncConditionsOnSelfConditionDetails {
+            id: 0,
+            condition: Condition::Stun,
+            time_remaining,
+            newly_applied: false,
+        }
+    }
+
+    #[test]
+    fn name_and_party_are_trimmed() {
+        let mut payload = update_player("  Name  ", " abc ");
+        validate_update_player(Sid::new(), "test", &mut payload).unwrap();
+        assert_eq!(payload.name, "Name");
+        assert_eq!(payload.party, "abc");
+    }
+
+    #[test]
+    fn long_name_is_truncated() {
+        let mut payload = update_player(&"a".repeat(MAX_NAME_LENGTH + 10), "abc");
+        validate_update_player(Sid::new(), "test", &mut payload).unwrap();
+        assert_eq!(payload.name.chars().count(), MAX_NAME_LENGTH);
+    }
+
+    #[test]
+    fn empty_or_long_party_is_rejected() {
+        let mut payload = update_player("Name", "   ");
+        assert_eq!(
+            validate_update_player(Sid::new(), "test", &mut payload),
+            Err(ValidationError::EmptyParty)
+        );
+
+        let mut payload = update_player("Name", &"a".repeat(MAX_PARTY_LENGTH + 1));
+        assert_eq!(
+            validate_update_player(Sid::new(), "test", &mut payload),
+            Err(ValidationError::PartyTooLong)
+        );
+    }
+
+    #[test]
+    fn out_of_range_position_is_clamped() {
+        let mut payload =
+            update_status(MAX_WORLD_COORDINATE * 2.0, 0.0, -MAX_WORLD_COORDINATE * 2.0);
+        validate_update_status(Sid::new(), "test", &mut payload).unwrap();
+        assert_eq!(payload.world_position_x, MAX_WORLD_COORDINATE);
+        assert_eq!(payload.world_position_z, -MAX_WORLD_COORDINATE);
+    }
+
+    #[test]
+    fn non_finite_status_is_rejected() {
+        let mut payload = update_status(f32::NAN, 0.0, 0.0);
+        assert_eq!(
+            validate_update_status(Sid::new(), "test", &mut payload),
+            Err(ValidationError::NonFinitePosition)
+        );
+
+        let mut payload = update_status(0.0, 0.0, 0.0);
+        payload.rotation = Some(f32::INFINITY);
+        assert_eq!(
+            validate_update_status(Sid::new(), "test", &mut payload),
+            Err(ValidationError::NonFiniteRotation)
+        );
+    }
+
+    #[test]
+    fn unknown_movement_flags_are_masked() {
+        let mut payload = update_status(0.0, 0.0, 0.0);
+        payload.movement_flags = Some(u8::MAX);
+        validate_update_status(Sid::new(), "test", &mut payload).unwrap();
+        assert_eq!(payload.movement_flags, Some(MovementFlags::ALL));
+    }
+
+    #[test]
+    fn extra_data_length_is_limited() {
+        let mut payload = StartMechanicPayload {
+            request_id: "id".to_string(),
+            extra_data: Some("a".repeat(MAX_EXTRA_DATA_LENGTH)),
+            ..Default::default()
+        };
+        assert_eq!(validate_start_mechanic(&mut payload), Ok(()));
+
+        payload.extra_data = Some("a".repeat(MAX_EXTRA_DATA_LENGTH + 1));
+        assert_eq!(
+            validate_start_mechanic(&mut payload),
+            Err(ValidationError::ExtraDataTooLong)
+        );
+    }
+
+    #[test]
+    fn non_finite_start_mechanic_is_rejected() {
+        let mut payload = StartMechanicPayload {
+            request_id: "id".to_string(),
+            world_position_x: Some(f32::NAN),
+            ..Default::default()
+        };
+        assert_eq!(
+            validate_start_mechanic(&mut payload),
+            Err(ValidationError::NonFinitePosition)
+        );
+
+        let mut payload = StartMechanicPayload {
+            request_id: "id".to_string(),
+            vertical_extent_below: Some(f32::INFINITY),
+            vertical_extent_above: Some(1.0),
+            ..Default::default()
+        };
+        assert_eq!(
+            validate_start_mechanic(&mut payload),
+            Err(ValidationError::InvalidVerticalExtent)
+        );
+    }
+
+    #[test]
+    fn conditions_are_truncated_filtered_and_clamped() {
+        let mut payload = SyncConditionsOnSelfPayload {
+            conditions: (0..MAX_CONDITIONS + 5).map(|_| condition(1.0)).collect(),
+        };
+        validate_sync_conditions_on_self(Sid::new(), "test", &mut payload);
+        assert_eq!(payload.conditions.len(), MAX_CONDITIONS);
+
+        let mut payload = SyncConditionsOnSelfPayload {
+            conditions: vec![
+                condition(f32::NAN),
+                condition(-1.0),
+                condition(MAX_CONDITION_TIME_REMAINING * 2.0),
+            ],
+        };
+        validate_sync_conditions_on_self(Sid::new(), "test", &mut payload);
+        let time_remaining: Vec<f32> = payload
+            .conditions
+            .iter()
+            .map(|c| c.time_remaining)
+            .collect();
+        assert_eq!(time_remaining, vec![0.0, MAX_CONDITION_TIME_REMAINING]);
+    }
+}